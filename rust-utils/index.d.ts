@@ -3,25 +3,366 @@
 
 /* auto-generated by NAPI-RS */
 
+/** Happs and UIs referenced by an installed app */
+export interface AppReferences {
+  /** Hex encoded sha256 hash of the .happ file of the app */
+  happSha256?: string
+  /** Hex encoded sha256 hash of the ui.zip of the app's current UI */
+  uiZipSha256?: string
+}
+/** Entries removed from the store by a garbage collection */
+export interface GarbageCollection {
+  removedHapps: Array<string>
+  removedUis: Array<string>
+  /** Number of bytes freed on disk */
+  freedSize: number
+}
+/** Disk usage of the store in bytes */
+export interface BundleStoreUsage {
+  happCount: number
+  happsSize: number
+  uiCount: number
+  uisSize: number
+  /** Size of the happs and UIs that no installed app references */
+  unreferencedSize: number
+}
 export function overwriteConfig(adminPort: number, keystoreConnectionUrl: string, bootstrapServerUrl: string, signalingServerUrl: string, configPath: string, allowedOrigin: string, iceServerUrls?: Array<string> | undefined | null): string
 export function defaultConductorConfig(adminPort: number, keystoreConnectionUrl: string, bootstrapServerUrl: string, signalingServerUrl: string, conductorEnvironmentPath: string, allowedOrigin: string, useDpki: boolean, iceServerUrls?: Array<string> | undefined | null): string
 export function decodeHappOrWebhapp(happOrWebhappBytes: Array<number>): Promise<HappAndUiBytes>
 export function readAndDecodeHappOrWebhapp(path: string): Promise<HappAndUiBytes>
-export function saveWebhapp(path: string, uiTargetDir: string): Promise<string>
+/**
+ * Writes the happ of a webhapp to a temporary .happ file and unzips the UI from memory into
+ * ui_target_dir
+ */
+export function saveWebhapp(path: string, uiTargetDir: string): Promise<TempHapp>
+/**
+ * Removes temporary .happ files of save_webhapp that have not been modified for
+ * older_than_secs seconds, one hour by default. Such files are left behind if the launcher
+ * quits before their handles are released. Returns the number of removed files.
+ */
+export function cleanupStaleTempHapps(olderThanSecs?: number | undefined | null): number
+/**
+ * Decodes a happ or webhapp file by streaming it from disk instead of reading it into memory.
+ * The .happ file is written to happ_target_path and, for webhapps, the UI assets are unzipped
+ * into ui_target_dir. Resources are copied in chunks, so only paths and hashes are returned.
+ * If decoding fails, neither the .happ file nor the UI assets are left behind.
+ */
+export function decodeHappOrWebhappToPaths(path: string, happTargetPath: string, uiTargetDir: string): Promise<HappAndUiPaths>
+/**
+ * Extracts the ui.zip of a webhapp into target_dir and returns the sha256 hashes of the zip
+ * and of every extracted file, hashed while extracting
+ */
+export function extractUi(uiZipBytes: Array<number>, targetDir: string): Promise<UiAssetManifest>
+/**
+ * Compares the files in a UI directory with the file hashes of its UiAssetManifest and
+ * reports files that are missing, that have been modified or that have been added
+ */
+export function verifyUiDirectory(dir: string, fileHashes: Record<string, string>): Promise<UiDirectoryReport>
+/**
+ * Inspects a happ or webhapp, given as bytes or as the path to the file, to show the user
+ * what it contains before it gets installed. A file is read as a stream and the UI of a
+ * webhapp file is skipped rather than loaded into memory.
+ */
+export function inspectBundle(bytesOrPath: Array<number> | string): Promise<BundleInspection>
+/**
+ * Computes the DNA hash each role of a happ or webhapp would get when installing it with the
+ * given network seed. Modifiers in role_modifiers, keyed by role name, override those of the
+ * role in the happ manifest.
+ */
+export function computeDnaHashes(happOrWebhappBytes: Array<number>, networkSeed?: string | undefined | null, roleModifiers?: Record<string, DnaModifiersOverride> | undefined | null): Promise<Array<RoleDnaHash>>
+/** Layout of a host config json file */
+export const enum HostConfigFormat {
+  /** Fields nested under a top-level "v2" key */
+  V2 = 'v2',
+  /** Fields nested under a top-level "v3" key */
+  V3 = 'v3',
+  /** v3 fields at the top level of the file */
+  Flat = 'flat'
+}
+/**
+ * Contents of a host config json file, as far as they are relevant for importing
+ * the host seed into lair
+ */
+export interface HostConfig {
+  format: HostConfigFormat
+  /**
+   * URL safe base64 encoded locked device bundle, regardless of the base64
+   * variant used in the file
+   */
+  deviceBundle: string
+  /** Derivation indices leading from the device bundle to the host seed */
+  deviceDerivationPath: Array<number>
+  revocationPubKey?: string
+  holoportId?: string
+  /** Base64 encoded public key the host seed is expected to have */
+  initialHostPubKey?: string
+  registrationCode?: string
+  adminEmail?: string
+}
+/** Parses a host config json file in the v2, v3 or flat format */
+export function parseHostConfig(json: string): HostConfig
+export interface EncryptedSeed {
+  /** Base64 (url safe, no padding) encoded locked seed bundle */
+  bundle: string
+  /** Types of the ciphers that can unlock the seed bundle, e.g. "PwHash" or "SecurityQuestions" */
+  ciphers: Array<string>
+  /**
+   * Base64 encoded ed25519 public key of the seed. Only missing for seeds migrated
+   * from key files that predate public keys.
+   */
+  pubKey?: string
+  /** Derivation indices leading from the root seed to this seed, empty for the root seed */
+  derivationPath: Array<number>
+  /** Short checksum of the public key to tell keys apart at a glance */
+  checksum?: string
+  /** Weakest argon2 limits among the passphrase based ciphers of the seed bundle */
+  pwHashLimits?: PwHashLimitsInfo
+}
+/**
+ * Argon2 limit presets for encrypting seeds. Stronger limits make brute forcing
+ * the passphrase more expensive but also take longer to lock and unlock seeds.
+ */
+export const enum PwHashLimitsPreset {
+  Interactive = 'interactive',
+  Moderate = 'moderate',
+  Sensitive = 'sensitive'
+}
+/** Argon2 limits a seed bundle has been encrypted with */
+export interface PwHashLimitsInfo {
+  /** Name of the matching preset, e.g. "moderate", or "custom" if no preset matches */
+  preset: string
+  memLimit: number
+  opsLimit: number
+  /** Whether the limits are weaker than the default "moderate" preset */
+  weak: boolean
+}
+/**
+ * Returns the argon2 limits of a base64 encoded locked seed bundle, e.g. to warn
+ * the user before unlocking a seed that has been encrypted with weak limits
+ */
+export function getPwHashLimits(lockedBundle: string): PwHashLimitsInfo
+/** Describes how the seeds of a key file are encrypted */
+export interface KeyFileKdf {
+  /** Hash applied to passphrases before the key derivation */
+  passphrasePrehash: string
+  /** Password hashing algorithm deriving the encryption key from the passphrase */
+  algorithm: string
+  /** Cipher the seeds are encrypted with */
+  cipher: string
+  /** Format of the locked seed bundles */
+  seedBundleFormat: string
+}
+/**
+ * Versioned key file. Together with their derivation paths, the seeds form the
+ * derivation tree below the root seed.
+ */
 export interface KeyFile {
-  rootSeed: string
-  revocationSeed: string
-  deviceSeedsSeed: string
-  revocationKey0: string
-  deviceSeed0: string
+  formatVersion: number
+  kdf: KeyFileKdf
+  rootSeed: EncryptedSeed
+  revocationSeed: EncryptedSeed
+  deviceSeedsSeed: EncryptedSeed
+  /** Revocation keys derived from the revocation seed, keyed by their derivation index */
+  revocationKeys: Record<string, EncryptedSeed>
+  /** Device seeds derived from the device seeds seed, keyed by their derivation index */
+  deviceSeeds: Record<string, EncryptedSeed>
+  /** Creation time in seconds since the Unix epoch */
   timestamp: number
 }
 /**
- * Generates root seed, revocation key and device seed
+ * Describes which seeds to derive from the root seed.
+ *
+ * Revocation and device seed indices are taken from the explicit index lists if
+ * provided, otherwise the first `count` indices are used. Without a spec, a single
+ * revocation key and device seed are derived at index 0.
+ */
+export interface DerivationSpec {
+  /** Defaults to 0 */
+  revocationSeedIndex?: number
+  /** Defaults to 1 */
+  deviceSeedsSeedIndex?: number
+  revocationKeyCount?: number
+  revocationKeyIndices?: Array<number>
+  deviceSeedCount?: number
+  deviceSeedIndices?: Array<number>
+}
+/** Three security questions and their answers */
+export interface SecurityQuestions {
+  questions: Array<string>
+  answers: Array<string>
+}
+/**
+ * Passphrases for individual seeds. Seeds without a passphrase in here are
+ * encrypted with the default passphrase. The revocation key and device seed
+ * passphrases apply to all derived revocation keys and device seeds respectively.
+ */
+export interface SeedPassphrases {
+  rootSeed?: string
+  revocationSeed?: string
+  deviceSeedsSeed?: string
+  revocationKey?: string
+  deviceSeed?: string
+}
+export interface KeyGenerationOptions {
+  derivation?: DerivationSpec
+  passphrases?: SeedPassphrases
+  /** If provided, every seed can additionally be unlocked by answering these questions */
+  securityQuestions?: SecurityQuestions
+  /** Defaults to the "moderate" preset */
+  pwHashLimits?: PwHashLimitsPreset
+}
+/** Progress of the seed encryptions while generating initial seeds */
+export interface SeedEncryptionProgress {
+  /** Name of the seed, e.g. "root seed" or "device seed 0" */
+  seedName: string
+  derivationPath: Array<number>
+  /** Whether the encryption of the seed has just started or finished */
+  finished: boolean
+  /** Number of seeds encrypted so far */
+  encryptedCount: number
+  totalCount: number
+}
+/**
+ * Generates root seed, revocation seed and device seeds seed as well as the
+ * revocation keys and device seeds described by the derivation spec
+ *
+ * All seeds are encrypted with the given passphrase unless a separate passphrase
+ * is specified for them in the options. A few seeds are encrypted concurrently and
+ * the optional progress callback is called whenever the encryption of a seed
+ * starts or finishes.
+ */
+export function generateInitialSeeds(passphrase: string, options?: KeyGenerationOptions | undefined | null, progress?: (err: Error | null, progress: SeedEncryptionProgress) => any | undefined | null): Promise<KeyFile>
+/**
+ * Unlocks the device seeds seed of a KeyFile and derives the device seed at the
+ * given index from it, e.g. to onboard an additional device from the same root seed
+ *
+ * The derived device seed is encrypted with the same passphrase and, unless
+ * specified otherwise, the "moderate" argon2 limits.
+ */
+export function deriveDeviceSeed(keyFileJsonOrDeviceSeedsSeedB64: string, passphrase: string, index: number, pwHashLimits?: PwHashLimitsPreset | undefined | null): Promise<EncryptedSeed>
+/**
+ * Parses and validates the JSON of a key file, migrating key files of older
+ * format versions to the current one
+ */
+export function parseKeyFile(keyFileJson: string): Promise<KeyFile>
+/** Validates the key file and serializes it to JSON for storing it on disk */
+export function serializeKeyFile(keyFile: KeyFile): Promise<string>
+/**
+ * Unlocks the root seed of a KeyFile and returns it as a 24 word mnemonic that
+ * can be written down as a paper backup
+ */
+export function exportRootSeedMnemonic(keyFileJsonOrRootSeedB64: string, passphrase: string): Promise<string>
+/**
+ * Restores the root seed from a mnemonic and re-derives the same seeds as
+ * generate_initial_seeds did for the same derivation spec
+ *
+ * The seeds are encrypted with the given passphrase unless a separate passphrase
+ * is specified for them in the options.
+ */
+export function restoreKeyFileFromMnemonic(mnemonic: string, passphrase: string, options?: KeyGenerationOptions | undefined | null, progress?: (err: Error | null, progress: SeedEncryptionProgress) => any | undefined | null): Promise<KeyFile>
+/**
+ * One of several Shamir secret shares of the root seed, meant to be handed to a
+ * trusted party. Any `threshold` shares of the same split rebuild the root seed.
+ */
+export interface SeedShare {
+  formatVersion: number
+  /** Index of the share, starting at 1 */
+  index: number
+  threshold: number
+  shareCount: number
+  /** Base64 (url safe, no padding) encoded share */
+  share: string
+  /** Short checksum of the share to detect typos and corrupted shares */
+  checksum: string
+  /** Base64 encoded ed25519 public key of the root seed, to verify the rebuilt seed */
+  rootSeedPubKey: string
+}
+/**
+ * Unlocks the root seed of a KeyFile and splits it into `share_count` shares
+ * for social recovery, any `threshold` of which are needed to rebuild it
+ */
+export function splitRootSeedIntoShares(keyFileJsonOrRootSeedB64: string, passphrase: string, shareCount: number, threshold: number): Promise<Array<SeedShare>>
+/**
+ * Rebuilds the root seed from shares and re-derives the same seeds as
+ * generate_initial_seeds did for the same derivation spec
+ *
+ * The seeds are encrypted with the given passphrase unless a separate passphrase
+ * is specified for them in the options.
+ */
+export function restoreKeyFileFromShares(shares: Array<SeedShare>, passphrase: string, options?: KeyGenerationOptions | undefined | null, progress?: (err: Error | null, progress: SeedEncryptionProgress) => any | undefined | null): Promise<KeyFile>
+/** Signature of a revocation key authorizing a revocation, as expected by DeepKey */
+export interface RevocationAuthorization {
+  /**
+   * Index of the revocation key in the authorized signers of the DeepKey change rule,
+   * at most 255
+   */
+  authorizerIndex: number
+  /** Base64 encoded ed25519 public key of the revocation key */
+  authorizerPubKey: string
+  signature: Array<number>
+}
+/** Payload for revoking a key registration in DeepKey */
+export interface KeyRevocationPayload {
+  /** Base64 encoded action hash of the key registration to revoke */
+  priorKeyRegistration: string
+  revocationAuthorization: Array<RevocationAuthorization>
+}
+/**
+ * Payload for replacing a key registration with a new key in DeepKey
+ *
+ * The new key still needs to sign the key generation itself, which has to happen
+ * wherever its private key is held, i.e. in lair.
+ */
+export interface KeyUpdatePayload {
+  revocation: KeyRevocationPayload
+  /** Base64 encoded public key of the key replacing the revoked one */
+  newKey: string
+}
+/**
+ * Returns the base64 encoded public key of the revocation key at the given index,
+ * which checks at the same time that the revocation key can be unlocked
+ */
+export function getRevocationKeyPubKey(keyFileJsonOrRevocationKeyB64: string, passphrase: string, index: number): Promise<string>
+/**
+ * Signs the revocation of the given key registration with the revocation key
+ * at the given index
+ */
+export function createKeyRevocation(keyFileJsonOrRevocationKeyB64: string, passphrase: string, index: number, priorKeyRegistration: string, authorizerIndex: number): Promise<KeyRevocationPayload>
+/**
+ * Signs the revocation of the given key registration with the revocation key
+ * at the given index in order to replace it with the new key
+ */
+export function createKeyUpdate(keyFileJsonOrRevocationKeyB64: string, passphrase: string, index: number, priorKeyRegistration: string, authorizerIndex: number, newKey: string): Promise<KeyUpdatePayload>
+/**
+ * Derives the revocation key following the given one from the revocation seed,
+ * to be used once the current revocation key has been used or compromised
  *
- * Use a single passphrase for the whole file for starters
+ * The new revocation key is encrypted with the same passphrase and, unless
+ * specified otherwise, the "moderate" argon2 limits.
  */
-export function generateInitialSeeds(passphrase: string): Promise<KeyFile>
+export function rotateRevocationKey(keyFileJsonOrRevocationSeedB64: string, passphrase: string, currentIndex: number, pwHashLimits?: PwHashLimitsPreset | undefined | null): Promise<EncryptedSeed>
+/** Where to collect a seed bundle passphrase from if it is not passed directly */
+export const enum PassphraseSourceKind {
+  /**
+   * The passphrase callback, which is passed the description of the passphrase
+   * to collect and returns it, optionally as a promise
+   */
+  Callback = 'callback',
+  /** The environment variable named by envVar */
+  Env = 'env',
+  /**
+   * The first line read from the file descriptor fd, which is left open. The
+   * standard streams 0, 1 and 2 are not accepted.
+   */
+  Fd = 'fd',
+  /** An interactive pinentry prompt */
+  Pinentry = 'pinentry'
+}
+export interface PassphraseSource {
+  kind: PassphraseSourceKind
+  envVar?: string
+  fd?: number
+}
 export interface ZomeCallUnsignedNapi {
   cellId: Array<Array<number>>
   zomeName: string
@@ -47,12 +388,269 @@ export interface HappAndUiBytes {
   happBytes: Array<number>
   uiBytes?: Array<number>
 }
+/** Locations and sha256 hashes of a happ or webhapp file decoded to disk */
+export interface HappAndUiPaths {
+  happPath: string
+  /** Hex encoded sha256 hash of the .happ file */
+  happSha256: string
+  /** Directory the UI assets were unzipped to, if the file is a webhapp */
+  uiPath?: string
+  /** Hex encoded sha256 hash of the ui.zip the UI assets were unzipped from */
+  uiZipSha256?: string
+}
+/** Hashes of a UI as it was extracted from its ui.zip */
+export interface UiAssetManifest {
+  /** Hex encoded sha256 hash of the ui.zip */
+  uiZipSha256: string
+  /**
+   * Hex encoded sha256 hashes of the extracted files, keyed by their path relative to the
+   * UI directory with `/` as the path separator on every platform
+   */
+  files: Record<string, string>
+}
+/** Files of a UI directory that differ from its UiAssetManifest */
+export interface UiDirectoryReport {
+  missing: Array<string>
+  modified: Array<string>
+  extra: Array<string>
+}
+/** Contents of a happ or webhapp file, as shown to the user before installing it */
+export interface BundleInspection {
+  name: string
+  description?: string
+  roles: Array<RoleInspection>
+  /** Locations of the happ and the UI inside the webhapp, if the file is a webhapp */
+  webhapp?: WebhappLocations
+}
+/**
+ * Locations of the happ and the UI of a webhapp. Bundled resources are given by their
+ * path inside the webhapp, other locations by their local path or url.
+ */
+export interface WebhappLocations {
+  happLocation: string
+  uiLocation: string
+}
+/** If, how and when a cell is created for a role, see CellProvisioning of holochain_types */
+export const enum CellProvisioningStrategy {
+  Create = 'create',
+  UseExisting = 'use_existing',
+  CloneOnly = 'clone_only'
+}
+export interface RoleInspection {
+  name: string
+  provisioning: CellProvisioningStrategy
+  /** Whether cell creation is deferred, only set for the create strategy */
+  deferred?: boolean
+  /** Whether the depended-upon app is protected, only set for the use_existing strategy */
+  protected?: boolean
+  cloneLimit: number
+  /** DNA hash the DNA of the role is expected to have before modifiers are applied */
+  installedHash?: string
+  /** The DNA of the role, if it is bundled into the happ */
+  dna?: DnaInspection
+}
+export interface DnaInspection {
+  name: string
+  networkSeed: string
+  /** DNA properties, decoded to json */
+  properties: any
+  /** Origin time in microseconds since the unix epoch */
+  originTime: number
+  /** Hash of the DNA with the modifiers of the role applied */
+  dnaHash: string
+  integrityZomes: Array<ZomeInspection>
+  coordinatorZomes: Array<ZomeInspection>
+}
+export interface ZomeInspection {
+  name: string
+  wasmHash: string
+}
+/** Modifiers overriding those a role's DNA is given in the happ manifest */
+export interface DnaModifiersOverride {
+  networkSeed?: string
+  /** DNA properties as json, replacing the properties of the manifest as a whole */
+  properties?: any
+  /** Origin time in microseconds since the unix epoch */
+  originTime?: number
+}
+export interface RoleDnaHash {
+  roleName: string
+  /**
+   * Hash of the DNA a cell of the role would be created with, if the role does not use
+   * an existing cell and its DNA is bundled into the happ
+   */
+  dnaHash?: string
+}
+/** Device seed imported into lair from a KeyFile */
+export interface ImportedDeviceSeed {
+  /**
+   * The tag the seed has been imported under, which differs from the requested
+   * tag if it has been renamed due to a conflict
+   */
+  tag: string
+  /** Base64 encoded public key of the imported seed, to be used as agent public key */
+  agentPubKey: string
+  exportable: boolean
+  deviceIndex: number
+  /** Derivation indices leading from the root seed to the device seed */
+  derivationPath: Array<number>
+  /**
+   * Whether the device seed was not contained in the key file and has been
+   * derived from the device seeds seed
+   */
+  derived: boolean
+}
+/**
+ * How to import a seed under a tag that is already taken in lair
+ *
+ * There is no strategy overwriting the existing seed, since lair can neither replace
+ * nor delete entries.
+ */
+export const enum TagConflictStrategy {
+  /** Fail the import */
+  Fail = 'fail',
+  /** Import the seed under the tag suffixed with the first free "#2" to "#9" */
+  Rename = 'rename'
+}
+export interface SeedImportOptions {
+  /** Whether lair allows exporting the imported seed again, defaults to false */
+  exportable?: boolean
+  /** Defaults to failing the import */
+  onTagConflict?: TagConflictStrategy
+}
+/** A seed imported into lair */
+export interface ImportedSeed {
+  /**
+   * The tag the seed has been imported under, which differs from the requested
+   * tag if it has been renamed due to a conflict
+   */
+  tag: string
+  /** Base64 encoded ed25519 public key, to be used as agent public key */
+  ed25519PubKey: string
+  /** URL safe base64 encoded x25519 public key */
+  x25519PubKey: string
+  exportable: boolean
+}
+/** A seed kept in lair only to encrypt imported seeds */
+export interface ImportHelperEntry {
+  tag: string
+  /** Base64 encoded ed25519 public key of the seed */
+  ed25519PubKey: string
+}
+/**
+ * Codes of the ways importing a seed into lair can fail. The message of an import
+ * error starts with its code followed by a colon, e.g. "TAG_EXISTS: ...".
+ */
+export const enum SeedImportErrorCode {
+  /** The seed bundle could not be unlocked, e.g. due to a wrong passphrase */
+  Unlock = 'UNLOCK',
+  /** The tag is already taken and the tag conflict strategy is to fail */
+  TagExists = 'TAG_EXISTS',
+  /** The tag and all the tags to rename the seed to are already taken */
+  NoFreeTag = 'NO_FREE_TAG',
+  /** The seed imports are encrypted for in transit could not be created */
+  TransportKey = 'TRANSPORT_KEY',
+  /** The seed could not be encrypted for the import */
+  Encrypt = 'ENCRYPT',
+  /** Lair rejected the import */
+  Import = 'IMPORT'
+}
+/** Result of deriving and importing the host seed of a host config json file */
+export interface HostSeedImport {
+  /** Base64 encoded public key of the seed imported into lair */
+  importedPubKey: string
+  /** The initial_host_pub_key of the json file, if it contains one */
+  expectedPubKey?: string
+  derivedPubKey: string
+  /** Derivation indices leading from the device bundle to the imported seed */
+  deviceDerivationPath: Array<number>
+  /** Whether the derived public key matches the expected one, if there is one */
+  pubKeyMatches?: boolean
+}
+/**
+ * Returns the three security questions of the first security questions cipher
+ * of the given base64 encoded locked seed bundle, if it contains one.
+ */
+export function getSecurityQuestions(deviceBundle: string): Promise<Array<string> | null>
+/**
+ * Handle to a temporary .happ file, which is deleted once the handle is released or
+ * garbage collected
+ */
+export class TempHapp {
+  get path(): string
+  /** Deletes the temporary .happ file */
+  release(): void
+}
+/**
+ * Content-addressed store of happ files and UI directories. Happs are stored as
+ * happs/[sha256].happ and UIs are extracted to uis/[sha256 of the ui.zip]/assets, so that
+ * apps using the same happ or UI share one copy on disk. Which app uses which entries is
+ * recorded as references, and entries that no app references can be garbage collected.
+ */
+export class BundleStore {
+  /** Opens the store in root_dir, the directory holding the happs and uis directories */
+  constructor(rootDir: string)
+  /** Stores the bytes of a .happ file and returns their hex encoded sha256 hash */
+  putHappBytes(happBytes: Array<number>): Promise<string>
+  /**
+   * Stores a copy of the .happ file at path and returns its hex encoded sha256 hash. The
+   * file is hashed while copying, without reading it into memory.
+   */
+  putHappFile(path: string): Promise<string>
+  /**
+   * Whether a .happ file with this hash is stored. With verify, the file is hashed again
+   * and only counts as stored if its hash still matches.
+   */
+  hasHapp(happSha256: string, verify: boolean): Promise<boolean>
+  /** Extracts the bytes of a ui.zip into the store and returns the manifest of the UI */
+  putUiBytes(uiZipBytes: Array<number>): Promise<UiAssetManifest>
+  /** Extracts the ui.zip file at path into the store and returns the manifest of the UI */
+  putUiFile(path: string): Promise<UiAssetManifest>
+  /**
+   * Moves the UI assets in dir, as unzipped from the ui.zip with this hash, into the store
+   * and returns the manifest of the UI. If a valid copy of the UI is stored already, dir is
+   * left as is. dir has to be on the same file system as the store.
+   */
+  putUiDirectory(uiZipSha256: string, dir: string): Promise<UiAssetManifest>
+  /**
+   * Whether the UI of the ui.zip with this hash is stored. With verify, the extracted files
+   * only count as stored if none of them is missing, modified or extra. UIs stored by
+   * earlier launcher versions have no manifest and are only checked for their assets.
+   */
+  hasUi(uiZipSha256: string, verify: boolean): Promise<boolean>
+  /** Records which happ and UI an installed app uses, replacing earlier references of the app */
+  setReferences(appId: string, references: AppReferences): Promise<void>
+  /** Removes the references of an app, e.g. after uninstalling it */
+  removeReferences(appId: string): Promise<void>
+  /**
+   * Replaces the references of all apps with those of the currently installed apps, e.g.
+   * as read from their metadata at startup. Garbage collection is only possible once the
+   * references have been seeded, so that the happs and UIs of apps installed before
+   * references were recorded are not mistaken for garbage.
+   */
+  seedReferences(references: Record<string, AppReferences>): Promise<void>
+  /** Number of installed apps referencing the happ or UI with this hash */
+  referenceCount(sha256: string): Promise<number>
+  /**
+   * Removes all happs and UIs that no installed app references, as well as leftovers of
+   * interrupted writes. Only references set on the store are taken into account, so this
+   * fails until the references have been seeded with seed_references.
+   */
+  collectGarbage(): Promise<GarbageCollection>
+  /** Reports how much disk space the stored happs and UIs take up */
+  diskUsage(): Promise<BundleStoreUsage>
+}
 export type JsLauncherLairClient = LauncherLairClient
 export class LauncherLairClient {
   constructor()
   static connect(connectionUrl: string, passphrase: string): Promise<LauncherLairClient>
   signZomeCall(zomeCallUnsignedJs: ZomeCallUnsignedNapi): Promise<ZomeCallNapi>
   seedExists(tag: string): Promise<boolean>
-  importLockedSeedBundle(importLockedSeedBundle: string, passphrase: string, tag: string): Promise<string>
-  deriveAndImportSeedFromJsonFile(path: string, passphrase?: string | undefined | null): Promise<string>
+  getSeedPubKey(tag: string): Promise<string | null>
+  importLockedSeedBundle(importLockedSeedBundle: string, passphrase: string, tag: string, options?: SeedImportOptions | undefined | null): Promise<ImportedSeed>
+  importLockedSeedBundleWithSecurityAnswers(importLockedSeedBundle: string, securityAnswers: Array<string>, tag: string, options?: SeedImportOptions | undefined | null): Promise<ImportedSeed>
+  findImportHelperEntries(): Promise<Array<ImportHelperEntry>>
+  importDeviceSeedFromKeyFile(keyFileJson: string, passphrase: string, deviceIndex: number, tag: string, options?: SeedImportOptions | undefined | null): Promise<ImportedDeviceSeed>
+  deriveAndImportSeedFromJsonFile(path: string, passphrase?: string | undefined | null, strict?: boolean | undefined | null, passphraseSource?: PassphraseSource | undefined | null, passphraseCallback?: (description: string) => string | Promise<string>): Promise<HostSeedImport>
+  importHostConfigSeed(hostConfig: HostConfig, passphrase?: string | undefined | null, strict?: boolean | undefined | null, passphraseSource?: PassphraseSource | undefined | null, passphraseCallback?: (description: string) => string | Promise<string>): Promise<HostSeedImport>
 }
@@ -252,12 +252,40 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { overwriteConfig, defaultConductorConfig, decodeHappOrWebhapp, readAndDecodeHappOrWebhapp, saveWebhapp, generateInitialSeeds, LauncherLairClient } = nativeBinding
+const { BundleStore, overwriteConfig, defaultConductorConfig, TempHapp, decodeHappOrWebhapp, readAndDecodeHappOrWebhapp, saveWebhapp, cleanupStaleTempHapps, decodeHappOrWebhappToPaths, extractUi, verifyUiDirectory, inspectBundle, computeDnaHashes, HostConfigFormat, parseHostConfig, PwHashLimitsPreset, getPwHashLimits, generateInitialSeeds, deriveDeviceSeed, parseKeyFile, serializeKeyFile, exportRootSeedMnemonic, restoreKeyFileFromMnemonic, splitRootSeedIntoShares, restoreKeyFileFromShares, getRevocationKeyPubKey, createKeyRevocation, createKeyUpdate, rotateRevocationKey, PassphraseSourceKind, CellProvisioningStrategy, TagConflictStrategy, SeedImportErrorCode, getSecurityQuestions, LauncherLairClient } = nativeBinding
 
+module.exports.BundleStore = BundleStore
 module.exports.overwriteConfig = overwriteConfig
 module.exports.defaultConductorConfig = defaultConductorConfig
+module.exports.TempHapp = TempHapp
 module.exports.decodeHappOrWebhapp = decodeHappOrWebhapp
 module.exports.readAndDecodeHappOrWebhapp = readAndDecodeHappOrWebhapp
 module.exports.saveWebhapp = saveWebhapp
+module.exports.cleanupStaleTempHapps = cleanupStaleTempHapps
+module.exports.decodeHappOrWebhappToPaths = decodeHappOrWebhappToPaths
+module.exports.extractUi = extractUi
+module.exports.verifyUiDirectory = verifyUiDirectory
+module.exports.inspectBundle = inspectBundle
+module.exports.computeDnaHashes = computeDnaHashes
+module.exports.HostConfigFormat = HostConfigFormat
+module.exports.parseHostConfig = parseHostConfig
+module.exports.PwHashLimitsPreset = PwHashLimitsPreset
+module.exports.getPwHashLimits = getPwHashLimits
 module.exports.generateInitialSeeds = generateInitialSeeds
+module.exports.deriveDeviceSeed = deriveDeviceSeed
+module.exports.parseKeyFile = parseKeyFile
+module.exports.serializeKeyFile = serializeKeyFile
+module.exports.exportRootSeedMnemonic = exportRootSeedMnemonic
+module.exports.restoreKeyFileFromMnemonic = restoreKeyFileFromMnemonic
+module.exports.splitRootSeedIntoShares = splitRootSeedIntoShares
+module.exports.restoreKeyFileFromShares = restoreKeyFileFromShares
+module.exports.getRevocationKeyPubKey = getRevocationKeyPubKey
+module.exports.createKeyRevocation = createKeyRevocation
+module.exports.createKeyUpdate = createKeyUpdate
+module.exports.rotateRevocationKey = rotateRevocationKey
+module.exports.PassphraseSourceKind = PassphraseSourceKind
+module.exports.CellProvisioningStrategy = CellProvisioningStrategy
+module.exports.TagConflictStrategy = TagConflictStrategy
+module.exports.SeedImportErrorCode = SeedImportErrorCode
+module.exports.getSecurityQuestions = getSecurityQuestions
 module.exports.LauncherLairClient = LauncherLairClient
@@ -0,0 +1,1053 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use napi::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::decode_webapp::{
+    copy_and_hash, hash_ui_directory, ui_directory_report, unzip_file, UnzipOptions,
+};
+use crate::types::UiAssetManifest;
+use crate::utils::run_blocking;
+
+const HAPPS_DIRNAME: &str = "happs";
+const UIS_DIRNAME: &str = "uis";
+/// Directory of a stored UI that the assets are extracted into
+const UI_ASSETS_DIRNAME: &str = "assets";
+/// File of a stored UI holding its UiAssetManifest
+const UI_MANIFEST_FILENAME: &str = "manifest.json";
+/// File holding the hashes every installed app references
+const REFERENCES_FILENAME: &str = "bundle-store-references.json";
+/// Marker file created once the references have been seeded from the installed apps. Stores
+/// populated before references were recorded must not be garbage collected until then.
+const REFERENCES_SEEDED_FILENAME: &str = "bundle-store-references-seeded";
+/// Marker in the names of files and directories that are still being written
+const PARTIAL_MARKER: &str = ".partial-";
+/// Age after which a partial entry is a leftover of an interrupted write rather than a write
+/// in progress, one hour
+const STALE_PARTIAL_SECS: u64 = 60 * 60;
+
+/// Happs and UIs referenced by an installed app
+#[napi(object)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AppReferences {
+    /// Hex encoded sha256 hash of the .happ file of the app
+    pub happ_sha256: Option<String>,
+    /// Hex encoded sha256 hash of the ui.zip of the app's current UI
+    pub ui_zip_sha256: Option<String>,
+}
+
+/// Entries removed from the store by a garbage collection
+#[napi(object)]
+pub struct GarbageCollection {
+    pub removed_happs: Vec<String>,
+    pub removed_uis: Vec<String>,
+    /// Number of bytes freed on disk
+    pub freed_size: i64,
+}
+
+/// Disk usage of the store in bytes
+#[napi(object)]
+pub struct BundleStoreUsage {
+    pub happ_count: u32,
+    pub happs_size: i64,
+    pub ui_count: u32,
+    pub uis_size: i64,
+    /// Size of the happs and UIs that no installed app references
+    pub unreferenced_size: i64,
+}
+
+/// Content-addressed store of happ files and UI directories. Happs are stored as
+/// happs/[sha256].happ and UIs are extracted to uis/[sha256 of the ui.zip]/assets, so that
+/// apps using the same happ or UI share one copy on disk. Which app uses which entries is
+/// recorded as references, and entries that no app references can be garbage collected.
+#[napi]
+pub struct BundleStore {
+    store: Arc<Store>,
+}
+
+struct Store {
+    root_dir: PathBuf,
+    // serializes storing entries, updates of the references file and garbage collection
+    lock: Mutex<()>,
+}
+
+#[napi]
+impl BundleStore {
+    /// Opens the store in root_dir, the directory holding the happs and uis directories
+    #[napi(constructor)]
+    pub fn new(root_dir: String) -> Self {
+        Self {
+            store: Arc::new(Store {
+                root_dir: PathBuf::from(root_dir),
+                lock: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Stores the bytes of a .happ file and returns their hex encoded sha256 hash
+    #[napi]
+    pub async fn put_happ_bytes(&self, happ_bytes: Vec<u8>) -> Result<String> {
+        self.run(move |store| store.put_happ_bytes(happ_bytes))
+            .await
+    }
+
+    /// Stores a copy of the .happ file at path and returns its hex encoded sha256 hash. The
+    /// file is hashed while copying, without reading it into memory.
+    #[napi]
+    pub async fn put_happ_file(&self, path: String) -> Result<String> {
+        self.run(move |store| store.put_happ_file(path)).await
+    }
+
+    /// Whether a .happ file with this hash is stored. With verify, the file is hashed again
+    /// and only counts as stored if its hash still matches.
+    #[napi]
+    pub async fn has_happ(&self, happ_sha256: String, verify: bool) -> Result<bool> {
+        self.run(move |store| store.has_happ(happ_sha256, verify))
+            .await
+    }
+
+    /// Extracts the bytes of a ui.zip into the store and returns the manifest of the UI
+    #[napi]
+    pub async fn put_ui_bytes(&self, ui_zip_bytes: Vec<u8>) -> Result<UiAssetManifest> {
+        self.run(move |store| store.put_ui_bytes(ui_zip_bytes))
+            .await
+    }
+
+    /// Extracts the ui.zip file at path into the store and returns the manifest of the UI
+    #[napi]
+    pub async fn put_ui_file(&self, path: String) -> Result<UiAssetManifest> {
+        self.run(move |store| store.put_ui_file(path)).await
+    }
+
+    /// Moves the UI assets in dir, as unzipped from the ui.zip with this hash, into the store
+    /// and returns the manifest of the UI. If a valid copy of the UI is stored already, dir is
+    /// left as is. dir has to be on the same file system as the store.
+    #[napi]
+    pub async fn put_ui_directory(
+        &self,
+        ui_zip_sha256: String,
+        dir: String,
+    ) -> Result<UiAssetManifest> {
+        self.run(move |store| store.put_ui_directory(ui_zip_sha256, dir))
+            .await
+    }
+
+    /// Whether the UI of the ui.zip with this hash is stored. With verify, the extracted files
+    /// only count as stored if none of them is missing, modified or extra. UIs stored by
+    /// earlier launcher versions have no manifest and are only checked for their assets.
+    #[napi]
+    pub async fn has_ui(&self, ui_zip_sha256: String, verify: bool) -> Result<bool> {
+        self.run(move |store| store.has_ui(ui_zip_sha256, verify))
+            .await
+    }
+
+    /// Records which happ and UI an installed app uses, replacing earlier references of the app
+    #[napi]
+    pub async fn set_references(&self, app_id: String, references: AppReferences) -> Result<()> {
+        self.run(move |store| store.set_references(app_id, references))
+            .await
+    }
+
+    /// Removes the references of an app, e.g. after uninstalling it
+    #[napi]
+    pub async fn remove_references(&self, app_id: String) -> Result<()> {
+        self.run(move |store| store.remove_references(app_id)).await
+    }
+
+    /// Replaces the references of all apps with those of the currently installed apps, e.g.
+    /// as read from their metadata at startup. Garbage collection is only possible once the
+    /// references have been seeded, so that the happs and UIs of apps installed before
+    /// references were recorded are not mistaken for garbage.
+    #[napi]
+    pub async fn seed_references(&self, references: HashMap<String, AppReferences>) -> Result<()> {
+        self.run(move |store| store.seed_references(references))
+            .await
+    }
+
+    /// Number of installed apps referencing the happ or UI with this hash
+    #[napi]
+    pub async fn reference_count(&self, sha256: String) -> Result<u32> {
+        self.run(move |store| store.reference_count(sha256)).await
+    }
+
+    /// Removes all happs and UIs that no installed app references, as well as leftovers of
+    /// interrupted writes. Only references set on the store are taken into account, so this
+    /// fails until the references have been seeded with seed_references. Happs and UIs stored
+    /// since the references were last written are kept, as the app storing them may not have
+    /// set its references yet, and so are leftovers younger than an hour.
+    #[napi]
+    pub async fn collect_garbage(&self) -> Result<GarbageCollection> {
+        self.run(|store| store.collect_garbage()).await
+    }
+
+    /// Reports how much disk space the stored happs and UIs take up
+    #[napi]
+    pub async fn disk_usage(&self) -> Result<BundleStoreUsage> {
+        self.run(|store| store.disk_usage()).await
+    }
+}
+
+impl BundleStore {
+    /// Runs an operation of the store on the blocking thread pool, since all of them do
+    /// file IO and most of them hash files
+    async fn run<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&Store) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let store = self.store.clone();
+        run_blocking(move || operation(&store)).await
+    }
+}
+
+impl Store {
+    fn put_happ_bytes(&self, happ_bytes: Vec<u8>) -> Result<String> {
+        let happ_sha256 = hex::encode(Sha256::digest(&happ_bytes));
+        let _guard = self.lock()?;
+        if self.happ_is_valid(&happ_sha256)? {
+            touch(&self.happ_path(&happ_sha256))?;
+            return Ok(happ_sha256);
+        }
+        let partial_path = self.partial_happ_path()?;
+        copy_and_hash(&mut happ_bytes.as_slice(), &partial_path)?;
+        self.move_happ_into_place(&partial_path, &happ_sha256)?;
+        Ok(happ_sha256)
+    }
+
+    fn put_happ_file(&self, path: String) -> Result<String> {
+        let mut happ_file = fs::File::open(&path)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to open happ file: {}", e)))?;
+        let partial_path = self.partial_happ_path()?;
+        let happ_sha256 = copy_and_hash(&mut happ_file, &partial_path)?;
+        let _guard = self.lock()?;
+        if self.happ_is_valid(&happ_sha256)? {
+            let _ = fs::remove_file(&partial_path);
+            touch(&self.happ_path(&happ_sha256))?;
+            return Ok(happ_sha256);
+        }
+        self.move_happ_into_place(&partial_path, &happ_sha256)?;
+        Ok(happ_sha256)
+    }
+
+    fn has_happ(&self, happ_sha256: String, verify: bool) -> Result<bool> {
+        validate_sha256(&happ_sha256)?;
+        if verify {
+            return self.happ_is_valid(&happ_sha256);
+        }
+        Ok(self.happ_path(&happ_sha256).is_file())
+    }
+
+    fn put_ui_bytes(&self, ui_zip_bytes: Vec<u8>) -> Result<UiAssetManifest> {
+        let ui_zip_sha256 = hex::encode(Sha256::digest(&ui_zip_bytes));
+        self.put_ui(ui_zip_sha256, io::Cursor::new(ui_zip_bytes))
+    }
+
+    fn put_ui_file(&self, path: String) -> Result<UiAssetManifest> {
+        let mut ui_zip_file = fs::File::open(&path)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to open ui.zip file: {}", e)))?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut ui_zip_file, &mut hasher)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read ui.zip file: {}", e)))?;
+        ui_zip_file
+            .rewind()
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read ui.zip file: {}", e)))?;
+        self.put_ui(hex::encode(hasher.finalize()), ui_zip_file)
+    }
+
+    fn put_ui_directory(&self, ui_zip_sha256: String, dir: String) -> Result<UiAssetManifest> {
+        validate_sha256(&ui_zip_sha256)?;
+        self.store_ui(ui_zip_sha256, |assets_dir| {
+            let files = hash_ui_directory(Path::new(&dir))?;
+            fs::rename(&dir, assets_dir).map_err(|e| {
+                napi::Error::from_reason(format!("Failed to move UI assets into the store: {}", e))
+            })?;
+            Ok(files)
+        })
+    }
+
+    fn has_ui(&self, ui_zip_sha256: String, verify: bool) -> Result<bool> {
+        validate_sha256(&ui_zip_sha256)?;
+        if !verify {
+            return Ok(self.ui_dir(&ui_zip_sha256).join(UI_ASSETS_DIRNAME).is_dir());
+        }
+        self.ui_is_valid(&ui_zip_sha256)
+    }
+
+    fn set_references(&self, app_id: String, references: AppReferences) -> Result<()> {
+        for sha256 in references
+            .happ_sha256
+            .iter()
+            .chain(references.ui_zip_sha256.iter())
+        {
+            validate_sha256(sha256)?;
+        }
+        let _guard = self.lock()?;
+        let mut all_references = self.read_references()?;
+        all_references.insert(app_id, references);
+        self.write_references(&all_references)
+    }
+
+    fn remove_references(&self, app_id: String) -> Result<()> {
+        let _guard = self.lock()?;
+        let mut all_references = self.read_references()?;
+        if all_references.remove(&app_id).is_some() {
+            self.write_references(&all_references)?;
+        }
+        Ok(())
+    }
+
+    fn seed_references(&self, references: HashMap<String, AppReferences>) -> Result<()> {
+        for app_references in references.values() {
+            for sha256 in app_references
+                .happ_sha256
+                .iter()
+                .chain(app_references.ui_zip_sha256.iter())
+            {
+                validate_sha256(sha256)?;
+            }
+        }
+        let _guard = self.lock()?;
+        self.write_references(&references.into_iter().collect())?;
+        fs::write(self.root_dir.join(REFERENCES_SEEDED_FILENAME), b"").map_err(|e| {
+            napi::Error::from_reason(format!(
+                "Failed to mark bundle store references as seeded: {}",
+                e
+            ))
+        })
+    }
+
+    fn reference_count(&self, sha256: String) -> Result<u32> {
+        let _guard = self.lock()?;
+        let count = self
+            .read_references()?
+            .values()
+            .filter(|references| {
+                references.happ_sha256.as_ref() == Some(&sha256)
+                    || references.ui_zip_sha256.as_ref() == Some(&sha256)
+            })
+            .count();
+        Ok(count as u32)
+    }
+
+    fn collect_garbage(&self) -> Result<GarbageCollection> {
+        let _guard = self.lock()?;
+        if !self.root_dir.join(REFERENCES_SEEDED_FILENAME).exists() {
+            return Err(napi::Error::from_reason("Refusing to collect garbage before the bundle store references have been seeded from the installed apps"));
+        }
+        let (referenced_happs, referenced_uis) = self.referenced_hashes()?;
+        let references_written = modified(&self.root_dir.join(REFERENCES_FILENAME))?;
+
+        let mut garbage_collection = GarbageCollection {
+            removed_happs: Vec::new(),
+            removed_uis: Vec::new(),
+            freed_size: 0,
+        };
+        for entry in self.list_entries(HAPPS_DIRNAME)? {
+            let happ_sha256 = entry.sha256.clone();
+            if happ_sha256
+                .as_ref()
+                .is_some_and(|sha256| referenced_happs.contains_key(sha256))
+                || !is_garbage(&entry, references_written)?
+            {
+                continue;
+            }
+            garbage_collection.freed_size += remove_entry(&entry.path)?;
+            garbage_collection.removed_happs.extend(happ_sha256);
+        }
+        for entry in self.list_entries(UIS_DIRNAME)? {
+            let ui_zip_sha256 = entry.sha256.clone();
+            if ui_zip_sha256
+                .as_ref()
+                .is_some_and(|sha256| referenced_uis.contains_key(sha256))
+                || !is_garbage(&entry, references_written)?
+            {
+                continue;
+            }
+            garbage_collection.freed_size += remove_entry(&entry.path)?;
+            garbage_collection.removed_uis.extend(ui_zip_sha256);
+        }
+
+        garbage_collection.removed_happs.sort();
+        garbage_collection.removed_uis.sort();
+        Ok(garbage_collection)
+    }
+
+    fn disk_usage(&self) -> Result<BundleStoreUsage> {
+        let _guard = self.lock()?;
+        let (referenced_happs, referenced_uis) = self.referenced_hashes()?;
+
+        let mut usage = BundleStoreUsage {
+            happ_count: 0,
+            happs_size: 0,
+            ui_count: 0,
+            uis_size: 0,
+            unreferenced_size: 0,
+        };
+        for entry in self.list_entries(HAPPS_DIRNAME)? {
+            let size = entry_size(&entry.path)?;
+            usage.happs_size += size;
+            if entry.sha256.is_some() {
+                usage.happ_count += 1;
+            }
+            if !entry
+                .sha256
+                .is_some_and(|sha256| referenced_happs.contains_key(&sha256))
+            {
+                usage.unreferenced_size += size;
+            }
+        }
+        for entry in self.list_entries(UIS_DIRNAME)? {
+            let size = entry_size(&entry.path)?;
+            usage.uis_size += size;
+            if entry.sha256.is_some() {
+                usage.ui_count += 1;
+            }
+            if !entry
+                .sha256
+                .is_some_and(|sha256| referenced_uis.contains_key(&sha256))
+            {
+                usage.unreferenced_size += size;
+            }
+        }
+        Ok(usage)
+    }
+}
+
+/// Entry of the happs or uis directory, with the hash it is stored under if it is a
+/// complete entry of the store
+struct StoreEntry {
+    path: PathBuf,
+    sha256: Option<String>,
+}
+
+impl Store {
+    fn lock(&self) -> Result<MutexGuard<'_, ()>> {
+        self.lock.lock().map_err(|_| {
+            napi::Error::from_reason("The bundle store is locked by an operation that panicked")
+        })
+    }
+
+    fn happ_path(&self, happ_sha256: &str) -> PathBuf {
+        self.root_dir
+            .join(HAPPS_DIRNAME)
+            .join(format!("{}.happ", happ_sha256))
+    }
+
+    fn ui_dir(&self, ui_zip_sha256: &str) -> PathBuf {
+        self.root_dir.join(UIS_DIRNAME).join(ui_zip_sha256)
+    }
+
+    fn happ_is_valid(&self, happ_sha256: &str) -> Result<bool> {
+        let happ_path = self.happ_path(happ_sha256);
+        if !happ_path.is_file() {
+            return Ok(false);
+        }
+        let mut happ_file = fs::File::open(&happ_path).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to open stored happ file: {}", e))
+        })?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut happ_file, &mut hasher).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to read stored happ file: {}", e))
+        })?;
+        Ok(hex::encode(hasher.finalize()) == happ_sha256)
+    }
+
+    fn partial_happ_path(&self) -> Result<PathBuf> {
+        let happs_dir = self.root_dir.join(HAPPS_DIRNAME);
+        fs::create_dir_all(&happs_dir).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to create happs directory: {}", e))
+        })?;
+        Ok(happs_dir.join(format!("{}{}.happ", PARTIAL_MARKER, nanoid::nanoid!(13))))
+    }
+
+    /// Moves a completely written happ file to its place in the store, replacing a corrupted
+    /// file stored under the same hash
+    fn move_happ_into_place(&self, partial_path: &Path, happ_sha256: &str) -> Result<()> {
+        fs::rename(partial_path, self.happ_path(happ_sha256)).map_err(|e| {
+            let _ = fs::remove_file(partial_path);
+            napi::Error::from_reason(format!("Failed to store happ file: {}", e))
+        })
+    }
+
+    fn ui_is_valid(&self, ui_zip_sha256: &str) -> Result<bool> {
+        let ui_dir = self.ui_dir(ui_zip_sha256);
+        let assets_dir = ui_dir.join(UI_ASSETS_DIRNAME);
+        if !assets_dir.is_dir() {
+            return Ok(false);
+        }
+        let manifest = match self.read_ui_manifest(ui_zip_sha256)? {
+            Some(manifest) => manifest,
+            None => return Ok(true),
+        };
+        let report = ui_directory_report(&assets_dir, manifest.files)?;
+        Ok(report.missing.is_empty() && report.modified.is_empty() && report.extra.is_empty())
+    }
+
+    fn read_ui_manifest(&self, ui_zip_sha256: &str) -> Result<Option<UiAssetManifest>> {
+        let manifest_path = self.ui_dir(ui_zip_sha256).join(UI_MANIFEST_FILENAME);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        let manifest_json = fs::read_to_string(&manifest_path)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read UI manifest: {}", e)))?;
+        let mut manifest: UiAssetManifest = serde_json::from_str(&manifest_json)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to parse UI manifest: {}", e)))?;
+        // manifests written on Windows by earlier versions key their files with `\`
+        manifest.files = manifest
+            .files
+            .into_iter()
+            .map(|(key, hash)| (key.replace('\\', "/"), hash))
+            .collect();
+        Ok(Some(manifest))
+    }
+
+    /// Extracts a ui.zip with the given hash into the store, unless a valid copy of it is
+    /// stored already
+    fn put_ui<R: Read + Seek>(&self, ui_zip_sha256: String, ui_zip: R) -> Result<UiAssetManifest> {
+        self.store_ui(ui_zip_sha256, |assets_dir| {
+            unzip_file(ui_zip, assets_dir.to_path_buf(), &UnzipOptions::default())
+                .map_err(|e| napi::Error::from_reason(format!("Failed to unzip ui.zip: {}", e)))
+        })
+    }
+
+    /// Stores the assets of the UI with the given hash with store_assets, which writes them to
+    /// the assets directory it is passed and returns their hashes, unless a valid copy of the
+    /// UI is stored already. Assets of an invalid copy are replaced, other files of the UI
+    /// directory like the launcher's icon.png are kept.
+    fn store_ui(
+        &self,
+        ui_zip_sha256: String,
+        store_assets: impl FnOnce(&Path) -> Result<HashMap<String, String>>,
+    ) -> Result<UiAssetManifest> {
+        let _guard = self.lock()?;
+        if self.ui_is_valid(&ui_zip_sha256)? {
+            if let Some(manifest) = self.read_ui_manifest(&ui_zip_sha256)? {
+                touch(&self.ui_dir(&ui_zip_sha256).join(UI_MANIFEST_FILENAME))?;
+                return Ok(manifest);
+            }
+        }
+
+        let ui_dir = self.ui_dir(&ui_zip_sha256);
+        let assets_dir = ui_dir.join(UI_ASSETS_DIRNAME);
+        if assets_dir.exists() {
+            fs::remove_dir_all(&assets_dir).map_err(|e| {
+                napi::Error::from_reason(format!("Failed to remove invalid UI assets: {}", e))
+            })?;
+        }
+        fs::create_dir_all(&ui_dir).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to create UI directory: {}", e))
+        })?;
+        let files = store_assets(&assets_dir)?;
+
+        let manifest = UiAssetManifest {
+            ui_zip_sha256,
+            files,
+        };
+        let manifest_json = serde_json::to_string(&manifest).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to serialize UI manifest: {}", e))
+        })?;
+        fs::write(ui_dir.join(UI_MANIFEST_FILENAME), manifest_json)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to write UI manifest: {}", e)))?;
+        Ok(manifest)
+    }
+
+    fn read_references(&self) -> Result<BTreeMap<String, AppReferences>> {
+        let references_path = self.root_dir.join(REFERENCES_FILENAME);
+        if !references_path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let references_json = fs::read_to_string(&references_path).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to read bundle store references: {}", e))
+        })?;
+        serde_json::from_str(&references_json).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to parse bundle store references: {}", e))
+        })
+    }
+
+    /// Writes the references to a temporary file first, so that an interrupted write never
+    /// leaves a truncated references file behind
+    fn write_references(&self, references: &BTreeMap<String, AppReferences>) -> Result<()> {
+        let references_json = serde_json::to_string_pretty(references).map_err(|e| {
+            napi::Error::from_reason(format!(
+                "Failed to serialize bundle store references: {}",
+                e
+            ))
+        })?;
+        fs::create_dir_all(&self.root_dir).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to create bundle store directory: {}", e))
+        })?;
+        let partial_path = self.root_dir.join(format!(
+            "{}{}{}",
+            REFERENCES_FILENAME,
+            PARTIAL_MARKER,
+            nanoid::nanoid!(13)
+        ));
+        fs::write(&partial_path, references_json).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to write bundle store references: {}", e))
+        })?;
+        fs::rename(&partial_path, self.root_dir.join(REFERENCES_FILENAME)).map_err(|e| {
+            let _ = fs::remove_file(&partial_path);
+            napi::Error::from_reason(format!("Failed to write bundle store references: {}", e))
+        })
+    }
+
+    /// Hashes of the happs and of the UIs referenced by installed apps, with the number of
+    /// apps referencing them
+    fn referenced_hashes(&self) -> Result<(HashMap<String, u32>, HashMap<String, u32>)> {
+        let mut happs = HashMap::new();
+        let mut uis = HashMap::new();
+        for references in self.read_references()?.into_values() {
+            if let Some(happ_sha256) = references.happ_sha256 {
+                *happs.entry(happ_sha256).or_insert(0) += 1;
+            }
+            if let Some(ui_zip_sha256) = references.ui_zip_sha256 {
+                *uis.entry(ui_zip_sha256).or_insert(0) += 1;
+            }
+        }
+        Ok((happs, uis))
+    }
+
+    fn list_entries(&self, dirname: &str) -> Result<Vec<StoreEntry>> {
+        let dir = self.root_dir.join(dirname);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let dir_entries = fs::read_dir(&dir).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to read {} directory: {}", dirname, e))
+        })?;
+
+        let mut entries = Vec::new();
+        for dir_entry in dir_entries {
+            let dir_entry = dir_entry.map_err(|e| {
+                napi::Error::from_reason(format!("Failed to read {} directory: {}", dirname, e))
+            })?;
+            let file_name = dir_entry.file_name().to_string_lossy().into_owned();
+            let sha256 = match dirname {
+                HAPPS_DIRNAME => file_name.strip_suffix(".happ").map(String::from),
+                _ => Some(file_name.clone()),
+            }
+            .filter(|sha256| validate_sha256(sha256).is_ok());
+
+            // anything else in the directories is either a leftover of an interrupted write
+            // or not managed by the store
+            if sha256.is_some() || file_name.contains(PARTIAL_MARKER) {
+                entries.push(StoreEntry {
+                    path: dir_entry.path(),
+                    sha256,
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn validate_sha256(sha256: &str) -> Result<()> {
+    if sha256.len() != 64
+        || !sha256
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    {
+        return Err(napi::Error::from_reason(format!(
+            "Invalid sha256 hash: {}",
+            sha256
+        )));
+    }
+    Ok(())
+}
+
+/// Whether an unreferenced entry can be removed. Partial entries are only removed once they
+/// are stale. Complete entries stored since the references were last written are kept, since
+/// the app storing them may not have set its references yet.
+fn is_garbage(entry: &StoreEntry, references_written: Option<SystemTime>) -> Result<bool> {
+    let mut entry_modified = modified(&entry.path)?;
+    if entry.sha256.is_some() && entry.path.is_dir() {
+        // the assets of a UI are written before its manifest, which is touched when the UI
+        // is stored again
+        entry_modified = entry_modified.max(modified(&entry.path.join(UI_MANIFEST_FILENAME))?);
+    }
+    let entry_modified = match entry_modified {
+        Some(entry_modified) => entry_modified,
+        None => return Ok(false),
+    };
+    if entry.sha256.is_none() {
+        let age = entry_modified.elapsed().unwrap_or_default();
+        return Ok(age >= Duration::from_secs(STALE_PARTIAL_SECS));
+    }
+    Ok(references_written.is_some_and(|references_written| entry_modified < references_written))
+}
+
+/// Modification time of a file or directory, if it exists
+fn modified(path: &Path) -> Result<Option<SystemTime>> {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.modified().map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| napi::Error::from_reason(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Sets the modification time of a stored file to now, to mark its entry as stored again
+fn touch(path: &Path) -> Result<()> {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+        .map_err(|e| {
+            napi::Error::from_reason(format!("Failed to update {}: {}", path.display(), e))
+        })
+}
+
+/// Size in bytes of a file or of all files in a directory
+fn entry_size(path: &Path) -> Result<i64> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len() as i64);
+    }
+    let mut size = 0;
+    let dir_entries = fs::read_dir(path).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    for dir_entry in dir_entries {
+        let dir_entry = dir_entry.map_err(|e| {
+            napi::Error::from_reason(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        size += entry_size(&dir_entry.path())?;
+    }
+    Ok(size)
+}
+
+/// Removes a file or directory and returns the number of bytes freed
+fn remove_entry(path: &Path) -> Result<i64> {
+    let size = entry_size(path)?;
+    let removed = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        _ => fs::remove_file(path),
+    };
+    removed.map_err(|e| {
+        napi::Error::from_reason(format!("Failed to remove {}: {}", path.display(), e))
+    })?;
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use napi::bindgen_prelude::block_on;
+    use std::io::Write;
+
+    fn test_store() -> (BundleStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "launcher_bundle_store_test_{}",
+            nanoid::nanoid!(13)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        (BundleStore::new(dir.to_str().unwrap().to_string()), dir)
+    }
+
+    fn ui_zip_bytes(index_html: &[u8]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file("index.html", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(index_html).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn sha256_hex(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    /// Waits for file system timestamps to advance, which may be as coarse as a few
+    /// milliseconds, so that references written next are newer than the stored entries
+    fn let_timestamps_advance() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    #[test]
+    fn happs_are_stored_under_their_hash_and_verified() {
+        let (store, dir) = test_store();
+        let happ = b"happ bytes".to_vec();
+
+        let happ_sha256 = block_on(store.put_happ_bytes(happ.clone())).unwrap();
+        assert_eq!(happ_sha256, sha256_hex(&happ));
+        assert_eq!(
+            fs::read(dir.join("happs").join(format!("{}.happ", happ_sha256))).unwrap(),
+            happ
+        );
+        assert!(block_on(store.has_happ(happ_sha256.clone(), true)).unwrap());
+
+        // A corrupted file still exists but fails verification, until it is stored again
+        fs::write(
+            dir.join("happs").join(format!("{}.happ", happ_sha256)),
+            b"corrupted",
+        )
+        .unwrap();
+        assert!(block_on(store.has_happ(happ_sha256.clone(), false)).unwrap());
+        assert!(!block_on(store.has_happ(happ_sha256.clone(), true)).unwrap());
+
+        let happ_path = dir.join("app.happ");
+        fs::write(&happ_path, &happ).unwrap();
+        assert_eq!(
+            block_on(store.put_happ_file(happ_path.to_str().unwrap().to_string())).unwrap(),
+            happ_sha256
+        );
+        assert!(block_on(store.has_happ(happ_sha256, true)).unwrap());
+        assert_eq!(fs::read_dir(dir.join("happs")).unwrap().count(), 1);
+
+        // Hashes are never used as paths without being validated
+        assert!(block_on(store.has_happ("../../etc/passwd".to_string(), false)).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn uis_are_extracted_under_the_hash_of_their_zip_and_verified() {
+        let (store, dir) = test_store();
+        let ui_zip = ui_zip_bytes(b"<html></html>");
+
+        let manifest = block_on(store.put_ui_bytes(ui_zip.clone())).unwrap();
+        assert_eq!(manifest.ui_zip_sha256, sha256_hex(&ui_zip));
+        assert_eq!(manifest.files["index.html"], sha256_hex(b"<html></html>"));
+        let assets_dir = dir.join("uis").join(&manifest.ui_zip_sha256).join("assets");
+        assert_eq!(
+            fs::read(assets_dir.join("index.html")).unwrap(),
+            b"<html></html>"
+        );
+        assert!(block_on(store.has_ui(manifest.ui_zip_sha256.clone(), true)).unwrap());
+
+        fs::write(assets_dir.join("index.html"), b"<html>tampered</html>").unwrap();
+        assert!(block_on(store.has_ui(manifest.ui_zip_sha256.clone(), false)).unwrap());
+        assert!(!block_on(store.has_ui(manifest.ui_zip_sha256.clone(), true)).unwrap());
+
+        // Storing the UI again replaces the tampered assets but keeps other files of the UI
+        let icon_path = dir
+            .join("uis")
+            .join(&manifest.ui_zip_sha256)
+            .join("icon.png");
+        fs::write(&icon_path, b"icon").unwrap();
+        let ui_zip_path = dir.join("ui.zip");
+        fs::write(&ui_zip_path, &ui_zip).unwrap();
+        block_on(store.put_ui_file(ui_zip_path.to_str().unwrap().to_string())).unwrap();
+        assert!(block_on(store.has_ui(manifest.ui_zip_sha256.clone(), true)).unwrap());
+        assert!(icon_path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn manifests_with_windows_separators_are_still_valid() {
+        let (store, dir) = test_store();
+        let ui_zip_sha256 = sha256_hex(b"ui.zip");
+        let ui_dir = dir.join("uis").join(&ui_zip_sha256);
+        fs::create_dir_all(ui_dir.join("assets").join("js")).unwrap();
+        fs::write(
+            ui_dir.join("assets").join("js").join("main.js"),
+            b"console.log('main')",
+        )
+        .unwrap();
+        let manifest = serde_json::json!({
+            "uiZipSha256": ui_zip_sha256,
+            "files": { "js\\main.js": sha256_hex(b"console.log('main')") },
+        });
+        fs::write(ui_dir.join("manifest.json"), manifest.to_string()).unwrap();
+
+        assert!(block_on(store.has_ui(ui_zip_sha256, true)).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unzipped_ui_directories_are_moved_into_the_store() {
+        let (store, dir) = test_store();
+        let ui_zip = ui_zip_bytes(b"<html></html>");
+        let unzipped_dir = dir.join("unzipped");
+        fs::create_dir_all(unzipped_dir.join("assets")).unwrap();
+        fs::write(unzipped_dir.join("index.html"), b"<html></html>").unwrap();
+
+        let manifest = block_on(store.put_ui_directory(
+            sha256_hex(&ui_zip),
+            unzipped_dir.to_str().unwrap().to_string(),
+        ))
+        .unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files["index.html"], sha256_hex(b"<html></html>"));
+        assert!(!unzipped_dir.exists());
+        assert!(block_on(store.has_ui(manifest.ui_zip_sha256.clone(), true)).unwrap());
+
+        // A valid copy is kept and the directory to store is left alone
+        fs::create_dir_all(&unzipped_dir).unwrap();
+        fs::write(unzipped_dir.join("index.html"), b"<html>other</html>").unwrap();
+        let stored_manifest = block_on(store.put_ui_directory(
+            manifest.ui_zip_sha256.clone(),
+            unzipped_dir.to_str().unwrap().to_string(),
+        ))
+        .unwrap();
+        assert_eq!(stored_manifest.files, manifest.files);
+        assert!(unzipped_dir.join("index.html").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unreferenced_entries_are_garbage_collected() {
+        let (store, dir) = test_store();
+        let shared_happ = block_on(store.put_happ_bytes(b"shared happ".to_vec())).unwrap();
+        let other_happ = block_on(store.put_happ_bytes(b"other happ".to_vec())).unwrap();
+        let ui = block_on(store.put_ui_bytes(ui_zip_bytes(b"<html></html>")))
+            .unwrap()
+            .ui_zip_sha256;
+        let old_ui = block_on(store.put_ui_bytes(ui_zip_bytes(b"<html>old</html>")))
+            .unwrap()
+            .ui_zip_sha256;
+        let leftover_path = dir.join("happs").join(".partial-leftover.happ");
+        fs::write(&leftover_path, b"partial").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&leftover_path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(STALE_PARTIAL_SECS))
+            .unwrap();
+        let in_progress_path = dir.join("happs").join(".partial-in-progress.happ");
+        fs::write(&in_progress_path, b"partial").unwrap();
+        let_timestamps_advance();
+        block_on(store.seed_references(HashMap::new())).unwrap();
+
+        let references = |happ: &str, ui: Option<&str>| AppReferences {
+            happ_sha256: Some(happ.to_string()),
+            ui_zip_sha256: ui.map(String::from),
+        };
+        block_on(
+            store.set_references("app-1".to_string(), references(&shared_happ, Some(&old_ui))),
+        )
+        .unwrap();
+        block_on(store.set_references("app-1".to_string(), references(&shared_happ, Some(&ui))))
+            .unwrap();
+        block_on(store.set_references("app-2".to_string(), references(&shared_happ, None)))
+            .unwrap();
+        block_on(store.set_references("app-3".to_string(), references(&other_happ, None))).unwrap();
+        block_on(store.remove_references("app-3".to_string())).unwrap();
+        assert_eq!(
+            block_on(store.reference_count(shared_happ.clone())).unwrap(),
+            2
+        );
+        assert_eq!(block_on(store.reference_count(old_ui.clone())).unwrap(), 0);
+
+        let usage = block_on(store.disk_usage()).unwrap();
+        assert_eq!(usage.happ_count, 2);
+        assert_eq!(usage.ui_count, 2);
+        assert!(usage.unreferenced_size > 0);
+
+        let garbage_collection = block_on(store.collect_garbage()).unwrap();
+        assert_eq!(garbage_collection.removed_happs, vec![other_happ.clone()]);
+        assert_eq!(garbage_collection.removed_uis, vec![old_ui.clone()]);
+        assert_eq!(
+            garbage_collection.freed_size,
+            usage.unreferenced_size - entry_size(&in_progress_path).unwrap()
+        );
+        assert!(block_on(store.has_happ(shared_happ, true)).unwrap());
+        assert!(!block_on(store.has_happ(other_happ, false)).unwrap());
+        assert!(block_on(store.has_ui(ui, true)).unwrap());
+        assert!(!block_on(store.has_ui(old_ui, false)).unwrap());
+        assert!(!leftover_path.exists());
+        assert!(in_progress_path.exists());
+
+        fs::remove_file(&in_progress_path).unwrap();
+        let usage = block_on(store.disk_usage()).unwrap();
+        assert_eq!(
+            (usage.happ_count, usage.ui_count, usage.unreferenced_size),
+            (1, 1, 0)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stores_populated_before_references_survive_garbage_collection() {
+        let (store, dir) = test_store();
+        let happ = block_on(store.put_happ_bytes(b"happ".to_vec())).unwrap();
+        let ui = block_on(store.put_ui_bytes(ui_zip_bytes(b"<html></html>")))
+            .unwrap()
+            .ui_zip_sha256;
+        let uninstalled_happ =
+            block_on(store.put_happ_bytes(b"uninstalled happ".to_vec())).unwrap();
+
+        // Without seeded references, nothing is known about which entries are in use
+        assert!(block_on(store.collect_garbage()).is_err());
+        let_timestamps_advance();
+        assert!(block_on(store.has_happ(happ.clone(), true)).unwrap());
+        assert!(block_on(store.has_ui(ui.clone(), true)).unwrap());
+        assert!(block_on(store.has_happ(uninstalled_happ.clone(), true)).unwrap());
+
+        let installed_apps = HashMap::from([(
+            "app".to_string(),
+            AppReferences {
+                happ_sha256: Some(happ.clone()),
+                ui_zip_sha256: Some(ui.clone()),
+            },
+        )]);
+        block_on(store.seed_references(installed_apps)).unwrap();
+
+        let garbage_collection = block_on(store.collect_garbage()).unwrap();
+        assert_eq!(garbage_collection.removed_happs, vec![uninstalled_happ]);
+        assert!(garbage_collection.removed_uis.is_empty());
+        assert!(block_on(store.has_happ(happ, true)).unwrap());
+        assert!(block_on(store.has_ui(ui, true)).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entries_stored_while_collecting_garbage_are_kept_until_referenced() {
+        let (store, dir) = test_store();
+        block_on(store.seed_references(HashMap::new())).unwrap();
+
+        let (happs, uis) = std::thread::scope(|scope| {
+            let put = scope.spawn(|| {
+                let mut happs = Vec::new();
+                let mut uis = Vec::new();
+                for i in 0..20 {
+                    let happ = format!("happ {}", i).into_bytes();
+                    happs.push(block_on(store.put_happ_bytes(happ)).unwrap());
+                    let ui_zip = ui_zip_bytes(format!("<html>{}</html>", i).as_bytes());
+                    uis.push(block_on(store.put_ui_bytes(ui_zip)).unwrap().ui_zip_sha256);
+                }
+                (happs, uis)
+            });
+            while !put.is_finished() {
+                let garbage_collection = block_on(store.collect_garbage()).unwrap();
+                assert!(garbage_collection.removed_happs.is_empty());
+                assert!(garbage_collection.removed_uis.is_empty());
+            }
+            put.join().unwrap()
+        });
+        for happ in &happs {
+            assert!(block_on(store.has_happ(happ.clone(), true)).unwrap());
+        }
+        for ui in &uis {
+            assert!(block_on(store.has_ui(ui.clone(), true)).unwrap());
+        }
+
+        // Storing an unreferenced entry again keeps it until the next reference write
+        let_timestamps_advance();
+        block_on(store.set_references("app".to_string(), AppReferences::default())).unwrap();
+        let_timestamps_advance();
+        assert_eq!(
+            block_on(store.put_happ_bytes(b"happ 0".to_vec())).unwrap(),
+            happs[0]
+        );
+        let garbage_collection = block_on(store.collect_garbage()).unwrap();
+        assert_eq!(garbage_collection.removed_happs.len(), 19);
+        assert!(!garbage_collection.removed_happs.contains(&happs[0]));
+        assert_eq!(garbage_collection.removed_uis.len(), 20);
+        assert!(block_on(store.has_happ(happs[0].clone(), true)).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,8 +1,25 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::fs;
-use holochain_types::app::AppBundle;
-use holochain_types::web_app::WebAppBundle;
-use crate::types::HappAndUiBytes;
+use std::io::{self, BufReader, Read, Write};
+use flate2::read::GzDecoder;
+use holo_hash::{DnaHashB64, WasmHash, WasmHashB64};
+use holochain_integrity_types::{DnaModifiersOpt, ZomeName};
+use holochain_types::app::{AppBundle, AppManifest, AppRoleManifest, CellProvisioning};
+use holochain_types::dna::{DnaBundle, DnaFile};
+use holochain_types::prelude::YamlProperties;
+use holochain_types::web_app::{WebAppBundle, WebAppManifest};
+use kitsune_p2p_timestamp::Timestamp;
+use mr_bundle::Location;
+use napi::bindgen_prelude::Either;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use crate::types::{
+    BundleInspection, CellProvisioningStrategy, DnaInspection, DnaModifiersOverride, HappAndUiBytes,
+    HappAndUiPaths, RoleDnaHash, RoleInspection, UiAssetManifest, UiDirectoryReport, WebhappLocations,
+    ZomeInspection,
+};
+use crate::utils::run_blocking;
 
 
 #[napi]
@@ -16,8 +33,57 @@ pub async fn read_and_decode_happ_or_webhapp(path: String) -> napi::Result<HappA
     decode_bytes(webhapp_bytes).await
 }
 
+/// Prefix of the temporary .happ files written by save_webhapp
+const TEMP_HAPP_PREFIX: &str = "launcher_app_to_install_";
+
+/// Age in seconds after which temporary .happ files are considered stale by default
+const STALE_TEMP_HAPP_SECS: u32 = 60 * 60;
+
+/// Handle to a temporary .happ file, which is deleted once the handle is released or
+/// garbage collected
+#[napi]
+pub struct TempHapp {
+    path: Option<PathBuf>,
+}
+
 #[napi]
-pub async fn save_webhapp(path: String, ui_target_dir: String) -> napi::Result<String> {
+impl TempHapp {
+    #[napi(getter)]
+    pub fn path(&self) -> napi::Result<String> {
+        let path = self.path.as_ref()
+            .ok_or(napi::Error::from_reason("Temporary happ file has already been released"))?;
+        path.to_str()
+            .map(String::from)
+            .ok_or(napi::Error::from_reason("Failed to convert temp happ path to string."))
+    }
+
+    /// Deletes the temporary .happ file
+    #[napi]
+    pub fn release(&mut self) -> napi::Result<()> {
+        if let Some(path) = self.path.take() {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(napi::Error::from_reason(format!("Failed to remove temporary happ file: {}", e)));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempHapp {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Writes the happ of a webhapp to a temporary .happ file and unzips the UI from memory into
+/// ui_target_dir
+#[napi]
+pub async fn save_webhapp(path: String, ui_target_dir: String) -> napi::Result<TempHapp> {
     let webhapp_bytes = fs::read(path)?;
     let web_app_bundle = WebAppBundle::decode(&webhapp_bytes)
         .map_err(|e| napi::Error::from_reason(format!("Failed to decode WebAppBundle: {}", e)))?;
@@ -30,110 +96,1322 @@ pub async fn save_webhapp(path: String, ui_target_dir: String) -> napi::Result<S
     let web_ui_zip_bytes = web_app_bundle.web_ui_zip_bytes().await
         .map_err(|e| napi::Error::from_reason(format!("Failed to extract ui zip bytes: {}", e)))?;
 
+    // the handle is created first so that a partially written file gets deleted as well
     let uid = nanoid::nanoid!(13);
-    let happ_path = std::env::temp_dir().join(format!("launcher_app_to_install_{}.happ", uid));
+    let temp_happ = TempHapp {
+        path: Some(std::env::temp_dir().join(format!("{}{}.happ", TEMP_HAPP_PREFIX, uid))),
+    };
+    app_bundle.write_to_file(temp_happ.path.as_ref().unwrap()).await
+        .map_err(|e| napi::Error::from_reason(format!("Failed to write .happ file: {}", e)))?;
+
+    // the UI is unzipped last, since a failed extraction leaves nothing behind and the .happ
+    // file is deleted with its handle if it fails
+    unzip_file(io::Cursor::new(web_ui_zip_bytes.into_owned().into_inner()), ui_target_dir.into(), &UnzipOptions::default())
+        .map_err(|e| napi::Error::from_reason(format!("Failed to unzip ui.zip: {}", e)))?;
+
+    Ok(temp_happ)
+}
+
+/// Removes temporary .happ files of save_webhapp that have not been modified for
+/// older_than_secs seconds, one hour by default. Such files are left behind if the launcher
+/// quits before their handles are released. Returns the number of removed files.
+#[napi]
+pub fn cleanup_stale_temp_happs(older_than_secs: Option<u32>) -> napi::Result<u32> {
+    let max_age = std::time::Duration::from_secs(older_than_secs.unwrap_or(STALE_TEMP_HAPP_SECS) as u64);
+    remove_stale_temp_happs(&std::env::temp_dir(), max_age)
+}
 
-    if !path_exists(&PathBuf::from(ui_target_dir.clone())) {
-        fs::create_dir_all(&ui_target_dir)?;
+fn remove_stale_temp_happs(dir: &Path, max_age: std::time::Duration) -> napi::Result<u32> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to read temp directory: {}", e)))?;
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !file_name.starts_with(TEMP_HAPP_PREFIX) || !file_name.ends_with(".happ") {
+            continue;
+        }
+        // files that cannot be inspected or removed may belong to another user
+        let is_stale = entry.metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified.elapsed().unwrap_or_default() >= max_age)
+            .unwrap_or(false);
+        if is_stale && fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
     }
+    Ok(removed)
+}
 
-    let ui_zip_path = PathBuf::from(ui_target_dir.clone()).join("ui.zip");
+/// Decodes a happ or webhapp file by streaming it from disk instead of reading it into memory.
+/// The .happ file is written to happ_target_path and, for webhapps, the UI assets are unzipped
+/// into ui_target_dir. Resources are copied in chunks, so only paths and hashes are returned.
+/// If decoding fails, neither the .happ file nor the UI assets are left behind.
+#[napi]
+pub async fn decode_happ_or_webhapp_to_paths(path: String, happ_target_path: String, ui_target_dir: String) -> napi::Result<HappAndUiPaths> {
+    // streaming and hashing the file is blocking IO
+    run_blocking(move || decode_to_paths(&path, happ_target_path, ui_target_dir)).await
+}
+
+fn decode_to_paths(path: &str, happ_target_path: String, ui_target_dir: String) -> napi::Result<HappAndUiPaths> {
+    // the ui.zip is kept outside of the UI directory, which has to be empty for unzipping
+    let ui_zip_path = std::env::temp_dir().join(format!("launcher_ui_{}.zip", nanoid::nanoid!(13)));
+
+    let result = write_happ_and_ui(path, Path::new(&happ_target_path), Path::new(&ui_target_dir), &ui_zip_path);
+    if ui_zip_path.exists() {
+        let _ = fs::remove_file(&ui_zip_path);
+    }
+    let (happ_sha256, ui_zip_sha256) = match result {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            // the UI is unzipped atomically, so only the .happ file can be left over
+            if Path::new(&happ_target_path).exists() {
+                let _ = fs::remove_file(&happ_target_path);
+            }
+            return Err(e);
+        }
+    };
+
+    Ok(HappAndUiPaths {
+        happ_path: happ_target_path,
+        happ_sha256,
+        ui_path: ui_zip_sha256.is_some().then_some(ui_target_dir),
+        ui_zip_sha256,
+    })
+}
 
-    // unzip and store UI
-    fs::write(ui_zip_path.clone(), web_ui_zip_bytes.into_owned().into_inner())
-        .map_err(|e| napi::Error::from_reason(format!("Failed to write Web UI Zip file: {}", e)))?;
+/// Writes the .happ file of a happ or webhapp file to happ_target_path and unzips the UI of a
+/// webhapp into ui_target_dir, by way of ui_zip_path. Returns the hashes of the .happ file and
+/// of the ui.zip.
+fn write_happ_and_ui(path: &str, happ_target_path: &Path, ui_target_dir: &Path, ui_zip_path: &Path) -> napi::Result<(String, Option<String>)> {
+    let mut reader = open_bundle(path)?;
 
-    let file = fs::File::open(ui_zip_path.clone())
+    let web_app_manifest = match read_bundle_manifest(&mut reader)? {
+        BundleManifest::WebApp(web_app_manifest) => web_app_manifest,
+        BundleManifest::App(_) => {
+            // a .happ file is stored as is and only needs to be hashed on the way
+            let mut happ_file = fs::File::open(path)
+                .map_err(|e| napi::Error::from_reason(format!("Failed to open happ file: {}", e)))?;
+            return Ok((copy_and_hash(&mut happ_file, happ_target_path)?, None));
+        }
+    };
+
+    let happ_resource = bundled_resource_path(web_app_manifest.happ_bundle_location(), "happ")?;
+    let ui_resource = bundled_resource_path(web_app_manifest.web_ui_location(), "UI")?;
+
+    let mut happ_sha256 = None;
+    let mut ui_zip_sha256 = None;
+
+    read_bundle_resources(&mut reader, |resource_path, resource| {
+        if resource_path == happ_resource {
+            happ_sha256 = Some(copy_and_hash(resource, happ_target_path)?);
+        } else if resource_path == ui_resource {
+            ui_zip_sha256 = Some(copy_and_hash(resource, ui_zip_path)?);
+        }
+        Ok(())
+    })?;
+
+    let happ_sha256 = happ_sha256
+        .ok_or(napi::Error::from_reason(format!("webhapp does not contain the happ resource {}", happ_resource.display())))?;
+    let ui_zip_sha256 = ui_zip_sha256
+        .ok_or(napi::Error::from_reason(format!("webhapp does not contain the UI resource {}", ui_resource.display())))?;
+
+    let ui_zip_file = fs::File::open(ui_zip_path)
         .map_err(|e| napi::Error::from_reason(format!("Failed to read Web UI Zip file: {}", e)))?;
+    unzip_file(ui_zip_file, ui_target_dir.to_path_buf(), &UnzipOptions::default())
+        .map_err(|e| napi::Error::from_reason(format!("Failed to unzip ui.zip: {}", e)))?;
+
+    Ok((happ_sha256, Some(ui_zip_sha256)))
+}
 
-    unzip_file(file, ui_target_dir.into())
+/// Extracts the ui.zip of a webhapp into target_dir and returns the sha256 hashes of the zip
+/// and of every extracted file, hashed while extracting
+#[napi]
+pub async fn extract_ui(ui_zip_bytes: Vec<u8>, target_dir: String) -> napi::Result<UiAssetManifest> {
+    let ui_zip_sha256 = hex::encode(Sha256::digest(&ui_zip_bytes));
+    let files = unzip_file(io::Cursor::new(ui_zip_bytes), target_dir.into(), &UnzipOptions::default())
         .map_err(|e| napi::Error::from_reason(format!("Failed to unzip ui.zip: {}", e)))?;
+    Ok(UiAssetManifest { ui_zip_sha256, files })
+}
 
-    fs::remove_file(ui_zip_path)
-        .map_err(|e| napi::Error::from_reason(format!("Failed to remove ui.zip after unzipping: {}", e)))?;
+/// Compares the files in a UI directory with the file hashes of its UiAssetManifest and
+/// reports files that are missing, that have been modified or that have been added
+#[napi]
+pub async fn verify_ui_directory(dir: String, file_hashes: HashMap<String, String>) -> napi::Result<UiDirectoryReport> {
+    ui_directory_report(Path::new(&dir), file_hashes)
+}
 
-    app_bundle.write_to_file(&happ_path).await
-        .map_err(|e| napi::Error::from_reason(format!("Failed to write .happ file: {}", e)))?;
+pub(crate) fn ui_directory_report(dir: &Path, file_hashes: HashMap<String, String>) -> napi::Result<UiDirectoryReport> {
+    let mut found_files = Vec::new();
+    list_files(dir, PathBuf::new(), &mut found_files)?;
 
-    let temp_folder_path_string = happ_path.as_os_str().to_str();
-    match temp_folder_path_string {
-        Some(str) => Ok(str.to_string()),
-        None => Err(napi::Error::from_reason("Failed to convert temp folder path to string."))
+    let mut report = UiDirectoryReport {
+        missing: Vec::new(),
+        modified: Vec::new(),
+        extra: Vec::new(),
+    };
+    for relative_path in &found_files {
+        let key = relative_path_key(relative_path);
+        match file_hashes.get(&key) {
+            Some(expected_hash) => {
+                if &hash_file(&dir.join(relative_path), &key)? != expected_hash {
+                    report.modified.push(key);
+                }
+            }
+            None => report.extra.push(key),
+        }
     }
+
+    let found_keys: Vec<String> = found_files.iter().map(|path| relative_path_key(path)).collect();
+    report.missing = file_hashes.into_keys().filter(|key| !found_keys.contains(key)).collect();
+
+    report.missing.sort();
+    report.modified.sort();
+    report.extra.sort();
+    Ok(report)
+}
+
+/// Hashes every file below dir, keyed by its path relative to dir like in a UiAssetManifest
+pub(crate) fn hash_ui_directory(dir: &Path) -> napi::Result<HashMap<String, String>> {
+    let mut files = Vec::new();
+    list_files(dir, PathBuf::new(), &mut files)?;
+    files.iter()
+        .map(|relative_path| {
+            let key = relative_path_key(relative_path);
+            let hash = hash_file(&dir.join(relative_path), &key)?;
+            Ok((key, hash))
+        })
+        .collect()
+}
+
+fn hash_file(path: &Path, key: &str) -> napi::Result<String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to open {}: {}", key, e)))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to read {}: {}", key, e)))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Collects the paths of all entries below dir that are not directories, relative to the
+/// directory the listing started at. Symlinks are listed but not followed.
+fn list_files(dir: &Path, relative_dir: PathBuf, files: &mut Vec<PathBuf>) -> napi::Result<()> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to read directory {}: {}", dir.display(), e)))?;
+    for entry in entries {
+        let entry = entry
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read directory {}: {}", dir.display(), e)))?;
+        let file_type = entry.file_type()
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read {}: {}", entry.path().display(), e)))?;
+        let relative_path = relative_dir.join(entry.file_name());
+        if file_type.is_dir() {
+            list_files(&entry.path(), relative_path, files)?;
+        } else {
+            files.push(relative_path);
+        }
+    }
+    Ok(())
 }
 
 /// Decodes bytes of a happ or webhapp file
 pub async fn decode_bytes(bytes: Vec<u8>) -> napi::Result<HappAndUiBytes> {
 
-    let (app_bundle_bytes, maybe_ui_zip_bytes) = match WebAppBundle::decode(&bytes) {
-        Ok(web_app_bundle) => {
-            // extract happ bundle
-            let app_bundle = web_app_bundle.happ_bundle().await
-                .map_err(|e| napi::Error::from_reason(format!("Failed to get happ bundle from webapp bundle bytes: {}", e)))?;
+    let (app_bundle, maybe_web_app_bundle) = decode_app_bundle(&bytes).await?;
 
-            let app_bundle_bytes = app_bundle.encode()
-                .map_err(|e| napi::Error::from_reason(format!("Failed to encode AppBundle: {}", e)))?;
+    let app_bundle_bytes = app_bundle.encode()
+        .map_err(|e| napi::Error::from_reason(format!("Failed to encode AppBundle: {}", e)))?;
 
+    let maybe_ui_zip_bytes = match maybe_web_app_bundle {
+        Some(web_app_bundle) => {
             // extracting ui.zip bytes
             let web_ui_zip_bytes = web_app_bundle.web_ui_zip_bytes().await
                 .map_err(|e| napi::Error::from_reason(format!("Failed to extract ui zip bytes: {}", e)))?;
+            Some(web_ui_zip_bytes.to_vec())
+        },
+        None => None,
+    };
 
-            (app_bundle_bytes, Some(web_ui_zip_bytes.to_vec()))
+    Ok(HappAndUiBytes {
+        happ_bytes: app_bundle_bytes,
+        ui_bytes: maybe_ui_zip_bytes,
+    })
+}
+
+/// Decodes bytes of a happ or webhapp file into the happ bundle, together with the
+/// webhapp bundle it was extracted from in case of a webhapp
+async fn decode_app_bundle(bytes: &[u8]) -> napi::Result<(AppBundle, Option<WebAppBundle>)> {
+    match WebAppBundle::decode(bytes) {
+        Ok(web_app_bundle) => {
+            // extract happ bundle
+            let app_bundle = web_app_bundle.happ_bundle().await
+                .map_err(|e| napi::Error::from_reason(format!("Failed to get happ bundle from webapp bundle bytes: {}", e)))?;
+
+            Ok((app_bundle, Some(web_app_bundle)))
         },
         Err(web_app_decode_error) => {
             // Try to decode happ bundle
-            match AppBundle::decode(&bytes) {
-                Ok(app_bundle) => {
-                    let app_bundle_bytes = app_bundle.encode()
-                        .map_err(|e| napi::Error::from_reason(format!("Failed to encode AppBundle: {}", e)))?;
-                    (app_bundle_bytes, None)
-                },
+            match AppBundle::decode(bytes) {
+                Ok(app_bundle) => Ok((app_bundle, None)),
                 Err(app_decode_error) => {
-                    return Err(napi::Error::from_reason(format!("Failed to decode bytes to either happ or webhapp bundle. Errors:\n{}\n{}", web_app_decode_error, app_decode_error)))
+                    Err(napi::Error::from_reason(format!("Failed to decode bytes to either happ or webhapp bundle. Errors:\n{}\n{}", web_app_decode_error, app_decode_error)))
                 }
             }
         }
+    }
+}
+
+/// Inspects a happ or webhapp, given as bytes or as the path to the file, to show the user
+/// what it contains before it gets installed. A file is read as a stream and the UI of a
+/// webhapp file is skipped rather than loaded into memory.
+#[napi]
+pub async fn inspect_bundle(bytes_or_path: Either<Vec<u8>, String>) -> napi::Result<BundleInspection> {
+    let (app_bundle, webhapp) = match bytes_or_path {
+        Either::A(bytes) => {
+            let (app_bundle, maybe_web_app_bundle) = decode_app_bundle(&bytes).await?;
+            (app_bundle, maybe_web_app_bundle.map(|web_app_bundle| webhapp_locations(web_app_bundle.manifest())))
+        }
+        // reading the file is blocking IO
+        Either::B(path) => run_blocking(move || read_app_bundle(&path)).await?,
     };
 
-    Ok(HappAndUiBytes {
-        happ_bytes: app_bundle_bytes,
-        ui_bytes: maybe_ui_zip_bytes,
+    let AppManifest::V1(manifest) = app_bundle.manifest().clone();
+    let mut roles = Vec::new();
+    for role in manifest.roles {
+        roles.push(inspect_role(&app_bundle, role).await?);
+    }
+
+    Ok(BundleInspection {
+        name: manifest.name,
+        description: manifest.description,
+        roles,
+        webhapp,
+    })
+}
+
+/// Reads the happ bundle of a happ or webhapp file, together with the locations of a webhapp.
+/// Of a webhapp, only the happ resource is read into memory, the UI is skipped.
+fn read_app_bundle(path: &str) -> napi::Result<(AppBundle, Option<WebhappLocations>)> {
+    let mut reader = open_bundle(path)?;
+    let web_app_manifest = match read_bundle_manifest(&mut reader)? {
+        BundleManifest::WebApp(web_app_manifest) => web_app_manifest,
+        BundleManifest::App(_) => {
+            // the whole file is the happ bundle
+            let app_bundle = AppBundle::decode(&fs::read(path)?)
+                .map_err(|e| napi::Error::from_reason(format!("Failed to decode happ bundle: {}", e)))?;
+            return Ok((app_bundle, None));
+        }
+    };
+
+    let happ_resource = bundled_resource_path(web_app_manifest.happ_bundle_location(), "happ")?;
+    let mut happ_bytes = None;
+
+    read_bundle_resources(&mut reader, |resource_path, resource| {
+        if resource_path == happ_resource {
+            let mut bytes = Vec::new();
+            resource.read_to_end(&mut bytes)
+                .map_err(|e| napi::Error::from_reason(format!("Failed to read bundle resource {}: {}", resource_path.display(), e)))?;
+            happ_bytes = Some(bytes);
+        }
+        Ok(())
+    })?;
+
+    let happ_bytes = happ_bytes
+        .ok_or(napi::Error::from_reason(format!("webhapp does not contain the happ resource {}", happ_resource.display())))?;
+    let app_bundle = AppBundle::decode(&happ_bytes)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to get happ bundle from webapp bundle: {}", e)))?;
+    Ok((app_bundle, Some(webhapp_locations(&web_app_manifest))))
+}
+
+fn webhapp_locations(web_app_manifest: &WebAppManifest) -> WebhappLocations {
+    WebhappLocations {
+        happ_location: location_to_string(&web_app_manifest.happ_bundle_location()),
+        ui_location: location_to_string(&web_app_manifest.web_ui_location()),
+    }
+}
+
+async fn inspect_role(app_bundle: &AppBundle, role: AppRoleManifest) -> napi::Result<RoleInspection> {
+    let (provisioning, deferred, protected) = match role.provisioning.clone().unwrap_or_default() {
+        CellProvisioning::Create { deferred } => (CellProvisioningStrategy::Create, Some(deferred), None),
+        CellProvisioning::UseExisting { protected } => (CellProvisioningStrategy::UseExisting, None, Some(protected)),
+        CellProvisioning::CloneOnly => (CellProvisioningStrategy::CloneOnly, None, None),
+    };
+
+    let dna = match resolve_role_dna(app_bundle, &role).await? {
+        Some(dna_file) => Some(inspect_dna(&dna_file)?),
+        None => None,
+    };
+
+    Ok(RoleInspection {
+        name: role.name,
+        provisioning,
+        deferred,
+        protected,
+        clone_limit: role.dna.clone_limit,
+        installed_hash: role.dna.installed_hash.map(|hash| hash.to_string()),
+        dna,
+    })
+}
+
+/// Computes the DNA hash each role of a happ or webhapp would get when installing it with the
+/// given network seed. Modifiers in role_modifiers, keyed by role name, override those of the
+/// role in the happ manifest.
+#[napi]
+pub async fn compute_dna_hashes(happ_or_webhapp_bytes: Vec<u8>, network_seed: Option<String>, role_modifiers: Option<HashMap<String, DnaModifiersOverride>>) -> napi::Result<Vec<RoleDnaHash>> {
+    let (app_bundle, _) = decode_app_bundle(&happ_or_webhapp_bytes).await?;
+
+    // the network seed applies to the same roles it applies to when installing the happ
+    let mut manifest = app_bundle.manifest().clone();
+    if let Some(network_seed) = network_seed {
+        manifest.set_network_seed(network_seed);
+    }
+    let roles = manifest.app_roles();
+
+    let mut role_modifiers = role_modifiers.unwrap_or_default();
+    if let Some(unknown_role) = role_modifiers.keys().find(|role_name| !roles.iter().any(|role| &role.name == *role_name)) {
+        return Err(napi::Error::from_reason(format!("The happ has no role named {}", unknown_role)));
+    }
+
+    let mut dna_hashes = Vec::new();
+    for mut role in roles {
+        if let Some(modifiers_override) = role_modifiers.remove(&role.name) {
+            override_modifiers(&mut role.dna.modifiers, modifiers_override)?;
+        }
+
+        let dna_hash = match role.provisioning.clone().unwrap_or_default() {
+            CellProvisioning::UseExisting { .. } => None,
+            _ => resolve_role_dna(&app_bundle, &role).await?
+                .map(|dna_file| DnaHashB64::from(dna_file.dna_hash().clone()).to_string()),
+        };
+
+        dna_hashes.push(RoleDnaHash {
+            role_name: role.name,
+            dna_hash,
+        });
+    }
+
+    Ok(dna_hashes)
+}
+
+fn override_modifiers(modifiers: &mut DnaModifiersOpt<YamlProperties>, modifiers_override: DnaModifiersOverride) -> napi::Result<()> {
+    if let Some(network_seed) = modifiers_override.network_seed {
+        modifiers.network_seed = Some(network_seed);
+    }
+    if let Some(properties) = modifiers_override.properties {
+        let properties = serde_json::from_value::<YamlProperties>(properties)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to convert DNA properties: {}", e)))?;
+        modifiers.properties = Some(properties);
+    }
+    if let Some(origin_time) = modifiers_override.origin_time {
+        modifiers.origin_time = Some(Timestamp(origin_time));
+    }
+    Ok(())
+}
+
+/// Builds the DNA of a role with the modifiers of the role applied, the same way the conductor
+/// does when installing the happ. Returns None if the DNA is not bundled into the happ.
+async fn resolve_role_dna(app_bundle: &AppBundle, role: &AppRoleManifest) -> napi::Result<Option<DnaFile>> {
+    let location = match &role.dna.location {
+        Some(location @ Location::Bundled(_)) => location,
+        _ => return Ok(None),
+    };
+    let modifiers = role.dna.modifiers.clone().serialized()
+        .map_err(|e| napi::Error::from_reason(format!("Failed to serialize DNA properties of role {}: {}", role.name, e)))?;
+
+    let dna_bundle_bytes = app_bundle.resolve(location).await
+        .map_err(|e| napi::Error::from_reason(format!("Failed to get DNA bundle of role {}: {}", role.name, e)))?;
+    let dna_bundle = DnaBundle::decode(&dna_bundle_bytes)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode DNA bundle of role {}: {}", role.name, e)))?;
+    let (dna_file, _original_hash) = dna_bundle.into_dna_file(modifiers).await
+        .map_err(|e| napi::Error::from_reason(format!("Failed to build DNA of role {}: {}", role.name, e)))?;
+
+    Ok(Some(dna_file))
+}
+
+fn inspect_dna(dna_file: &DnaFile) -> napi::Result<DnaInspection> {
+    let dna_def = dna_file.dna_def();
+    let properties = rmp_serde::from_slice(dna_def.modifiers.properties.bytes())
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode properties of DNA {}: {}", dna_def.name, e)))?;
+
+    Ok(DnaInspection {
+        name: dna_def.name.clone(),
+        network_seed: dna_def.modifiers.network_seed.clone(),
+        properties,
+        origin_time: dna_def.modifiers.origin_time.as_micros(),
+        dna_hash: DnaHashB64::from(dna_file.dna_hash().clone()).to_string(),
+        integrity_zomes: dna_def.integrity_zomes.iter()
+            .map(|(name, zome_def)| inspect_zome(name, zome_def.wasm_hash(name)))
+            .collect::<napi::Result<_>>()?,
+        coordinator_zomes: dna_def.coordinator_zomes.iter()
+            .map(|(name, zome_def)| inspect_zome(name, zome_def.wasm_hash(name)))
+            .collect::<napi::Result<_>>()?,
+    })
+}
+
+fn inspect_zome<E: std::fmt::Display>(name: &ZomeName, wasm_hash: Result<WasmHash, E>) -> napi::Result<ZomeInspection> {
+    let wasm_hash = wasm_hash
+        .map_err(|e| napi::Error::from_reason(format!("Failed to get wasm hash of zome {}: {}", name, e)))?;
+
+    Ok(ZomeInspection {
+        name: name.to_string(),
+        wasm_hash: WasmHashB64::from(wasm_hash).to_string(),
     })
 }
 
+fn location_to_string(location: &Location) -> String {
+    match location {
+        Location::Bundled(path) | Location::Path(path) => path.display().to_string(),
+        Location::Url(url) => url.clone(),
+    }
+}
+
 
 pub fn path_exists(path: &PathBuf) -> bool {
     std::path::Path::new(path).exists()
 }
 
-pub fn unzip_file(reader: fs::File, outpath: PathBuf) -> Result<(), String> {
-    let mut archive = match zip::ZipArchive::new(reader) {
-        Ok(a) => a,
-        Err(e) => return Err(format!("Failed to unpack zip archive: {}", e)),
+/// What to do with a zip entry that cannot be extracted safely
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnsafeEntryPolicy {
+    /// Fail the whole extraction
+    Reject,
+    /// Leave the entry out and extract the rest
+    Skip,
+}
+
+/// Limits and policies applied when extracting a UI zip. The limits guard against zip bombs,
+/// the policies decide what happens with symlinks and with entries whose path is absolute or
+/// would end up outside of the target directory.
+#[derive(Clone, Debug)]
+pub struct UnzipOptions {
+    pub max_entries: usize,
+    /// Maximum number of bytes of all entries together after decompression
+    pub max_total_size: u64,
+    /// Maximum ratio of the uncompressed to the compressed size of an entry
+    pub max_compression_ratio: u64,
+    /// Entries up to this many bytes after decompression are not checked against
+    /// max_compression_ratio, since small files such as source maps or sparse images often
+    /// compress far better than that and are covered by max_total_size anyway
+    pub min_ratio_checked_size: u64,
+    pub symlinks: UnsafeEntryPolicy,
+    pub unsafe_paths: UnsafeEntryPolicy,
+}
+
+impl Default for UnzipOptions {
+    fn default() -> Self {
+        UnzipOptions {
+            max_entries: 10_000,
+            max_total_size: 1024 * 1024 * 1024,
+            max_compression_ratio: 100,
+            min_ratio_checked_size: 1024 * 1024,
+            symlinks: UnsafeEntryPolicy::Reject,
+            unsafe_paths: UnsafeEntryPolicy::Reject,
+        }
+    }
+}
+
+/// Extracts a zip archive into outpath, which must either not exist yet or be an empty
+/// directory. Entries are extracted into a temporary directory next to outpath that is only
+/// renamed to outpath once every entry has been extracted, so a failed extraction never
+/// leaves a partially extracted directory behind.
+///
+/// Returns the hex encoded sha256 hashes of the extracted files, keyed by their path relative
+/// to outpath, computed while writing the files.
+pub fn unzip_file<R: Read + io::Seek>(reader: R, outpath: PathBuf, options: &UnzipOptions) -> Result<HashMap<String, String>, String> {
+    if outpath.exists() {
+        let mut entries = fs::read_dir(&outpath)
+            .map_err(|e| format!("Failed to read target directory {}: {}", outpath.display(), e))?;
+        if entries.next().is_some() {
+            return Err(format!("Target directory {} is not empty", outpath.display()));
+        }
+    }
+
+    let partial_path = partial_path(&outpath)?;
+    let result = extract_entries(reader, &partial_path, options)
+        .and_then(|file_hashes| move_into_place(&partial_path, &outpath).map(|_| file_hashes));
+    if result.is_err() && partial_path.exists() {
+        let _ = fs::remove_dir_all(&partial_path);
+    }
+    result
+}
+
+/// Returns a unique path in the same directory as outpath, so that renaming it to outpath
+/// does not need to move anything across file systems
+fn partial_path(outpath: &Path) -> Result<PathBuf, String> {
+    let dir_name = outpath.file_name()
+        .ok_or(format!("Invalid target directory {}", outpath.display()))?;
+    let parent = match outpath.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
     };
+    fs::create_dir_all(&parent)
+        .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+    Ok(parent.join(format!(".{}.partial-{}", dir_name.to_string_lossy(), nanoid::nanoid!(13))))
+}
 
+fn move_into_place(partial_path: &Path, outpath: &Path) -> Result<(), String> {
+    if outpath.exists() {
+        // checked to be empty before extracting
+        fs::remove_dir(outpath)
+            .map_err(|e| format!("Failed to replace target directory {}: {}", outpath.display(), e))?;
+    }
+    fs::rename(partial_path, outpath)
+        .map_err(|e| format!("Failed to move extracted files to {}: {}", outpath.display(), e))
+}
+
+fn extract_entries<R: Read + io::Seek>(reader: R, target_dir: &Path, options: &UnzipOptions) -> Result<HashMap<String, String>, String> {
+    let mut archive = zip::ZipArchive::new(reader)
+        .map_err(|e| format!("Failed to unpack zip archive: {}", e))?;
+    if archive.len() > options.max_entries {
+        return Err(format!("Zip archive has {} entries, more than the limit of {}", archive.len(), options.max_entries));
+    }
+    fs::create_dir_all(target_dir)
+        .map_err(|e| format!("Failed to create directory {}: {}", target_dir.display(), e))?;
+
+    let mut file_hashes = HashMap::new();
+    let mut remaining_size = options.max_total_size;
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let outpath = match file.enclosed_name() {
-            Some(path) => outpath.join(path).to_owned(),
-            None => continue,
-    };
+        let mut file = archive.by_index(i)
+            .map_err(|e| format!("Failed to read zip entry {}: {}", i, e))?;
 
-    if (&*file.name()).ends_with('/') {
-        fs::create_dir_all(&outpath).unwrap();
-    } else {
-        if let Some(p) = outpath.parent() {
-            if !p.exists() {
-                fs::create_dir_all(&p).unwrap();
+        let is_symlink = file.unix_mode().is_some_and(|mode| mode & 0o170000 == 0o120000);
+        if is_symlink {
+            match options.symlinks {
+                UnsafeEntryPolicy::Reject => return Err(format!("Zip entry {} is a symlink", file.name())),
+                UnsafeEntryPolicy::Skip => continue,
             }
         }
-        let mut outfile = fs::File::create(&outpath).unwrap();
-        std::io::copy(&mut file, &mut outfile).unwrap();
+        let relative_path = match file.enclosed_name() {
+            Some(path) => path.to_path_buf(),
+            None => match options.unsafe_paths {
+                UnsafeEntryPolicy::Reject => return Err(format!("Zip entry {} has an absolute path or a path outside of the target directory", file.name())),
+                UnsafeEntryPolicy::Skip => continue,
+            },
+        };
+        let entry_path = target_dir.join(&relative_path);
+
+        if file.is_dir() {
+            fs::create_dir_all(&entry_path)
+                .map_err(|e| format!("Failed to create directory {}: {}", entry_path.display(), e))?;
+            continue;
+        }
+
+        let size = file.size();
+        if size > remaining_size {
+            return Err(format!("Zip archive is larger than the limit of {} bytes when uncompressed", options.max_total_size));
+        }
+        if size > options.min_ratio_checked_size && size > file.compressed_size().saturating_mul(options.max_compression_ratio) {
+            return Err(format!("Zip entry {} exceeds the maximum compression ratio of {}", file.name(), options.max_compression_ratio));
+        }
+
+        if let Some(parent) = entry_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+        }
+        let outfile = fs::File::create(&entry_path)
+            .map_err(|e| format!("Failed to create {}: {}", entry_path.display(), e))?;
+        let mut writer = HashingWriter { inner: outfile, hasher: Sha256::new() };
+        // the sizes in the archive are not to be trusted, so no more than the declared size
+        // is decompressed
+        let written = io::copy(&mut (&mut file).take(size + 1), &mut writer)
+            .map_err(|e| format!("Failed to extract {}: {}", entry_path.display(), e))?;
+        if written > size {
+            return Err(format!("Zip entry {} is larger than its declared size", file.name()));
+        }
+        remaining_size -= written;
+        file_hashes.insert(relative_path_key(&relative_path), hex::encode(writer.hasher.finalize()));
+    }
+
+    Ok(file_hashes)
+}
+
+/// Key of a file in a UI asset manifest. Paths are joined with `/` on every platform, so that
+/// manifests stay valid when data is restored on another platform.
+fn relative_path_key(relative_path: &Path) -> String {
+    relative_path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Manifest of a bundle file, which is either a webhapp or a happ
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BundleManifest {
+    WebApp(WebAppManifest),
+    // only decoded to tell happs apart from files that are no bundle at all
+    App(#[allow(dead_code)] AppManifest),
+}
+
+/// Opens a bundle file for reading its gzipped msgpack encoding as a stream
+fn open_bundle(path: &str) -> napi::Result<BufReader<GzDecoder<BufReader<fs::File>>>> {
+    let file = fs::File::open(path)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to open bundle file: {}", e)))?;
+    Ok(BufReader::new(GzDecoder::new(BufReader::new(file))))
+}
+
+/// Reads the manifest at the start of a bundle, leaving the reader at the resources key.
+/// Bundles are encoded as a map of the manifest followed by the resources.
+fn read_bundle_manifest<R: Read>(reader: &mut R) -> napi::Result<BundleManifest> {
+    let field_count = rmp::decode::read_map_len(reader)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode bundle: {}", e)))?;
+    if field_count != 2 {
+        return Err(napi::Error::from_reason(format!("Failed to decode bundle: expected 2 fields but found {}", field_count)));
     }
+
+    expect_bundle_key(reader, "manifest")?;
+    BundleManifest::deserialize(&mut rmp_serde::Deserializer::new(reader))
+        .map_err(|_| napi::Error::from_reason("Failed to decode bundle manifest as either happ or webhapp manifest"))
+}
+
+fn expect_bundle_key<R: Read>(reader: &mut R, key: &str) -> napi::Result<()> {
+    let found_key = read_msgpack_string(reader)?;
+    if found_key != key {
+        return Err(napi::Error::from_reason(format!("Failed to decode bundle: expected {} field but found {}", key, found_key)));
     }
+    Ok(())
+}
+
+fn read_msgpack_string<R: Read>(reader: &mut R) -> napi::Result<String> {
+    let len = rmp::decode::read_str_len(reader)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode bundle: {}", e)))?;
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode bundle: {}", e)))?;
+    String::from_utf8(bytes)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode bundle: {}", e)))
+}
 
+/// Reads the resources that follow the manifest of a bundle one after the other and passes
+/// each of them to handle_resource. Whatever a handler leaves unread of a resource is skipped.
+fn read_bundle_resources<R: Read>(reader: &mut R, mut handle_resource: impl FnMut(&Path, &mut dyn Read) -> napi::Result<()>) -> napi::Result<()> {
+    expect_bundle_key(reader, "resources")?;
+    let resource_count = rmp::decode::read_map_len(reader)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to read bundle resources: {}", e)))?;
+    for _ in 0..resource_count {
+        let resource_path = PathBuf::from(read_msgpack_string(reader)?);
+        let resource_len = rmp::decode::read_bin_len(reader)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read bundle resource {}: {}", resource_path.display(), e)))?;
+        let mut resource = ExactReader::new((&mut *reader).take(resource_len as u64));
+
+        handle_resource(&resource_path, &mut resource)?;
+        io::copy(&mut resource, &mut io::sink())
+            .map_err(|e| napi::Error::from_reason(format!("Failed to read bundle resource {}: {}", resource_path.display(), e)))?;
+    }
     Ok(())
 }
 
+/// Returns the path of a resource that is expected to be bundled into the webhapp
+fn bundled_resource_path(location: Location, resource_name: &str) -> napi::Result<PathBuf> {
+    match location {
+        Location::Bundled(path) => Ok(path),
+        _ => Err(napi::Error::from_reason(format!("The {} of the webhapp is not bundled into the webhapp file", resource_name))),
+    }
+}
+
+/// Copies everything from the reader into a new file at target_path and returns the hex
+/// encoded sha256 hash of the copied bytes
+pub(crate) fn copy_and_hash<R: Read + ?Sized>(reader: &mut R, target_path: &Path) -> napi::Result<String> {
+    let file = fs::File::create(target_path)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to create {}: {}", target_path.display(), e)))?;
+    let mut writer = HashingWriter { inner: file, hasher: Sha256::new() };
+    io::copy(reader, &mut writer)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to write {}: {}", target_path.display(), e)))?;
+    writer.inner.flush()?;
+    Ok(hex::encode(writer.hasher.finalize()))
+}
+
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader over a resource of known length that fails if the bundle ends before the
+/// resource does, instead of silently returning a truncated resource
+struct ExactReader<R: Read> {
+    inner: io::Take<R>,
+}
+
+impl<R: Read> ExactReader<R> {
+    fn new(inner: io::Take<R>) -> Self {
+        ExactReader { inner }
+    }
+}
+
+impl<R: Read> Read for ExactReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read == 0 && !buf.is_empty() && self.inner.limit() > 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "bundle ends in the middle of a resource"));
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_types::app::{AppManifestV1, AppRoleDnaManifest};
+    use holochain_types::dna::{DnaManifest, ValidatedDnaManifest, ZomeManifest};
+    use holochain_types::web_app::{AppManifestLocation, WebAppManifestV1, WebUI};
+    use kitsune_p2p_timestamp::{HumanTimestamp, Timestamp};
+    use mr_bundle::{Bundle, ResourceBytes};
+    use napi::bindgen_prelude::block_on;
+
+    fn sha256_hex(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    fn dna_bundle_bytes() -> Vec<u8> {
+        let zome = |name: &str| ZomeManifest {
+            name: name.into(),
+            hash: None,
+            location: Location::Bundled(format!("{}.wasm", name).into()),
+            dependencies: None,
+            dylib: None,
+        };
+        let manifest = DnaManifest::current(
+            "dna".to_string(),
+            Some("dna-seed".to_string()),
+            None,
+            HumanTimestamp::Micros(Timestamp(1_700_000_000_000_000)),
+            vec![zome("integrity")],
+            vec![zome("coordinator")],
+            vec![],
+        );
+        let resources = vec![
+            ("integrity.wasm".into(), ResourceBytes::from(b"integrity wasm".to_vec())),
+            ("coordinator.wasm".into(), ResourceBytes::from(b"coordinator wasm".to_vec())),
+        ];
+        Bundle::new_unchecked(ValidatedDnaManifest::try_from(manifest).unwrap(), resources)
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    fn happ_bytes() -> Vec<u8> {
+        let properties: YamlProperties = serde_json::from_value(serde_json::json!({ "foo": "bar" })).unwrap();
+        let manifest = AppManifest::V1(AppManifestV1 {
+            name: "app".to_string(),
+            description: Some("An app".to_string()),
+            roles: vec![AppRoleManifest {
+                name: "role".to_string(),
+                provisioning: Some(CellProvisioning::Create { deferred: false }),
+                dna: AppRoleDnaManifest {
+                    location: Some(Location::Bundled("dna.dna".into())),
+                    modifiers: DnaModifiersOpt::none()
+                        .with_network_seed("role-seed".to_string())
+                        .with_properties(properties),
+                    installed_hash: None,
+                    clone_limit: 3,
+                },
+            }],
+            allow_deferred_memproofs: false,
+        });
+        let resources = vec![("dna.dna".into(), ResourceBytes::from(dna_bundle_bytes()))];
+        Bundle::new_unchecked(manifest, resources).unwrap().encode().unwrap()
+    }
+
+    fn ui_zip_bytes() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file("index.html", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(b"<html></html>").unwrap();
+        zip.add_directory("assets/", zip::write::FileOptions::default()).unwrap();
+        zip.start_file("assets/main.js", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(b"console.log('main')").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn webhapp_bytes(happ_bytes: &[u8], ui_zip_bytes: &[u8]) -> Vec<u8> {
+        let manifest = WebAppManifest::V1(WebAppManifestV1 {
+            name: "webapp".to_string(),
+            ui: WebUI { location: Location::Bundled("ui.zip".into()) },
+            happ_manifest: AppManifestLocation { location: Location::Bundled("app.happ".into()) },
+        });
+        let resources = vec![
+            ("app.happ".into(), ResourceBytes::from(happ_bytes.to_vec())),
+            ("ui.zip".into(), ResourceBytes::from(ui_zip_bytes.to_vec())),
+        ];
+        Bundle::new_unchecked(manifest, resources).unwrap().encode().unwrap()
+    }
+
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("launcher_decode_webapp_test_{}", nanoid::nanoid!(13)));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn webhapp_is_decoded_to_paths_matching_the_in_memory_decode() {
+        let dir = test_dir();
+        let (happ, ui_zip) = (happ_bytes(), ui_zip_bytes());
+        let webhapp_path = dir.join("app.webhapp");
+        fs::write(&webhapp_path, webhapp_bytes(&happ, &ui_zip)).unwrap();
+
+        let happ_target_path = dir.join("app.happ").to_str().unwrap().to_string();
+        let ui_target_dir = dir.join("ui").to_str().unwrap().to_string();
+        let decoded = block_on(decode_happ_or_webhapp_to_paths(
+            webhapp_path.to_str().unwrap().to_string(),
+            happ_target_path.clone(),
+            ui_target_dir.clone(),
+        ))
+        .unwrap();
+
+        assert_eq!(decoded.happ_sha256, sha256_hex(&happ));
+        assert_eq!(decoded.ui_zip_sha256, Some(sha256_hex(&ui_zip)));
+        assert_eq!(fs::read(&happ_target_path).unwrap(), happ);
+        assert!(AppBundle::decode(&fs::read(&happ_target_path).unwrap()).is_ok());
+        assert_eq!(decoded.ui_path.as_deref(), Some(ui_target_dir.as_str()));
+        let ui_dir = PathBuf::from(ui_target_dir);
+        assert_eq!(fs::read(ui_dir.join("index.html")).unwrap(), b"<html></html>");
+        assert!(ui_dir.join("assets/main.js").exists());
+        assert!(!ui_dir.join("ui.zip").exists());
+
+        // A plain happ file is copied as is
+        let happ_path = dir.join("plain.happ");
+        fs::write(&happ_path, &happ).unwrap();
+        let decoded = block_on(decode_happ_or_webhapp_to_paths(
+            happ_path.to_str().unwrap().to_string(),
+            dir.join("copied.happ").to_str().unwrap().to_string(),
+            dir.join("unused_ui").to_str().unwrap().to_string(),
+        ))
+        .unwrap();
+        assert_eq!(decoded.happ_sha256, sha256_hex(&happ));
+        assert_eq!(decoded.ui_path, None);
+        assert!(!dir.join("unused_ui").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn happ_is_removed_if_the_ui_fails_to_unzip() {
+        let dir = test_dir();
+        let webhapp_path = dir.join("app.webhapp");
+        fs::write(&webhapp_path, webhapp_bytes(&happ_bytes(), b"not a zip")).unwrap();
+
+        let happ_target_path = dir.join("app.happ");
+        let error = block_on(decode_happ_or_webhapp_to_paths(
+            webhapp_path.to_str().unwrap().to_string(),
+            happ_target_path.to_str().unwrap().to_string(),
+            dir.join("ui").to_str().unwrap().to_string(),
+        ))
+        .err()
+        .unwrap();
+        assert!(error.reason.starts_with("Failed to unzip ui.zip"));
+        assert!(!happ_target_path.exists());
+        assert!(!dir.join("ui").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saved_webhapp_happ_is_removed_on_release_and_drop() {
+        let dir = test_dir();
+        let webhapp_path = dir.join("app.webhapp");
+        fs::write(&webhapp_path, webhapp_bytes(&happ_bytes(), &ui_zip_bytes())).unwrap();
+        let save = |ui_dir: &str| {
+            block_on(save_webhapp(
+                webhapp_path.to_str().unwrap().to_string(),
+                dir.join(ui_dir).to_str().unwrap().to_string(),
+            ))
+            .unwrap()
+        };
+
+        let mut temp_happ = save("ui");
+        let happ_path = PathBuf::from(temp_happ.path().unwrap());
+        assert!(AppBundle::decode(&fs::read(&happ_path).unwrap()).is_ok());
+        // The UI is extracted from memory, without an intermediate ui.zip
+        assert!(dir.join("ui/index.html").exists());
+        assert!(!dir.join("ui/ui.zip").exists());
 
+        temp_happ.release().unwrap();
+        assert!(!happ_path.exists());
+        assert!(temp_happ.path().is_err());
+        temp_happ.release().unwrap();
 
+        let temp_happ = save("other_ui");
+        let happ_path = PathBuf::from(temp_happ.path().unwrap());
+        drop(temp_happ);
+        assert!(!happ_path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saved_webhapp_leaves_no_ui_behind_if_it_fails() {
+        let dir = test_dir();
+        let webhapp_path = dir.join("app.webhapp");
+        fs::write(&webhapp_path, webhapp_bytes(&happ_bytes(), b"not a zip")).unwrap();
+
+        let error = block_on(save_webhapp(
+            webhapp_path.to_str().unwrap().to_string(),
+            dir.join("ui").to_str().unwrap().to_string(),
+        ))
+        .err()
+        .unwrap();
+        assert!(error.reason.starts_with("Failed to unzip ui.zip"));
+        assert!(!dir.join("ui").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stale_temp_happs_are_removed() {
+        let dir = test_dir();
+        fs::write(dir.join("launcher_app_to_install_abc.happ"), b"happ").unwrap();
+        fs::write(dir.join("launcher_app_to_install_abc.txt"), b"other").unwrap();
+        fs::write(dir.join("other.happ"), b"other").unwrap();
+
+        let one_hour = std::time::Duration::from_secs(60 * 60);
+        assert_eq!(remove_stale_temp_happs(&dir, one_hour).unwrap(), 0);
+        assert_eq!(remove_stale_temp_happs(&dir, std::time::Duration::ZERO).unwrap(), 1);
+        assert!(!dir.join("launcher_app_to_install_abc.happ").exists());
+        assert!(dir.join("launcher_app_to_install_abc.txt").exists());
+        assert!(dir.join("other.happ").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_webhapp_fails_to_decode() {
+        let dir = test_dir();
+        let webhapp = webhapp_bytes(&happ_bytes(), &ui_zip_bytes());
+        let mut decompressed = Vec::new();
+        GzDecoder::new(webhapp.as_slice()).read_to_end(&mut decompressed).unwrap();
+        decompressed.truncate(decompressed.len() - 10);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&decompressed).unwrap();
+        let webhapp_path = dir.join("truncated.webhapp");
+        fs::write(&webhapp_path, encoder.finish().unwrap()).unwrap();
+
+        let result = block_on(decode_happ_or_webhapp_to_paths(
+            webhapp_path.to_str().unwrap().to_string(),
+            dir.join("app.happ").to_str().unwrap().to_string(),
+            dir.join("ui").to_str().unwrap().to_string(),
+        ));
+        assert!(result.is_err());
+        assert!(!dir.join("app.happ").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn unzip(zip_bytes: Vec<u8>, outpath: &Path, options: &UnzipOptions) -> Result<(), String> {
+        unzip_file(io::Cursor::new(zip_bytes), outpath.to_path_buf(), options).map(|_| ())
+    }
+
+    fn zip_with_files(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Asserts that nothing was extracted, neither into the target nor into a partial directory
+    fn assert_nothing_extracted(dir: &Path) {
+        let entries: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert!(entries.is_empty(), "unexpected entries {:?}", entries);
+    }
+
+    #[test]
+    fn ui_zip_is_extracted_into_missing_or_empty_directory() {
+        let dir = test_dir();
+        let ui_dir = dir.join("ui");
+        unzip(ui_zip_bytes(), &ui_dir, &UnzipOptions::default()).unwrap();
+        assert_eq!(fs::read(ui_dir.join("index.html")).unwrap(), b"<html></html>");
+        assert_eq!(fs::read(ui_dir.join("assets/main.js")).unwrap(), b"console.log('main')");
+
+        let empty_dir = dir.join("empty");
+        fs::create_dir(&empty_dir).unwrap();
+        unzip(ui_zip_bytes(), &empty_dir, &UnzipOptions::default()).unwrap();
+        assert!(empty_dir.join("index.html").exists());
+
+        // An existing UI is never overwritten
+        assert!(unzip(ui_zip_bytes(), &ui_dir, &UnzipOptions::default()).is_err());
+
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(entries.len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn extracted_ui_is_verified_against_its_manifest() {
+        let dir = test_dir();
+        let ui_dir = dir.join("ui");
+        let ui_zip = ui_zip_bytes();
+        let manifest = block_on(extract_ui(ui_zip.clone(), ui_dir.to_str().unwrap().to_string())).unwrap();
+
+        let main_js_key = "assets/main.js".to_string();
+        assert_eq!(manifest.ui_zip_sha256, sha256_hex(&ui_zip));
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(manifest.files["index.html"], sha256_hex(b"<html></html>"));
+        assert_eq!(manifest.files[&main_js_key], sha256_hex(b"console.log('main')"));
+
+        let verify = || block_on(verify_ui_directory(ui_dir.to_str().unwrap().to_string(), manifest.files.clone())).unwrap();
+        let report = verify();
+        assert!(report.missing.is_empty() && report.modified.is_empty() && report.extra.is_empty());
+
+        fs::write(ui_dir.join("index.html"), b"<html><script src=\"evil.js\"></script></html>").unwrap();
+        fs::write(ui_dir.join("assets/evil.js"), b"evil").unwrap();
+        fs::remove_file(ui_dir.join("assets/main.js")).unwrap();
+        let report = verify();
+        assert_eq!(report.missing, vec![main_js_key]);
+        assert_eq!(report.modified, vec!["index.html".to_string()]);
+        assert_eq!(report.extra, vec!["assets/evil.js".to_string()]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_ui_zip_fails_without_panicking() {
+        let dir = test_dir();
+        let outpath = dir.join("ui");
+        assert!(unzip(b"not a zip".to_vec(), &outpath, &UnzipOptions::default()).is_err());
+
+        let mut truncated = ui_zip_bytes();
+        truncated.truncate(truncated.len() / 2);
+        assert!(unzip(truncated, &outpath, &UnzipOptions::default()).is_err());
+
+        // Corrupting the compressed data of an entry fails its checksum
+        let content = b"console.log('main')".repeat(100);
+        let mut corrupt = zip_with_files(&[("main.js", &content)]);
+        corrupt[40] ^= 0xff;
+        assert!(unzip(corrupt, &outpath, &UnzipOptions::default()).is_err());
+
+        assert_nothing_extracted(&dir);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entries_outside_of_the_target_directory_are_rejected_or_skipped() {
+        let dir = test_dir();
+        let outpath = dir.join("ui");
+        for name in ["../evil.js", "assets/../../evil.js", "/tmp/evil.js"] {
+            let zip = zip_with_files(&[("index.html", b"<html></html>"), (name, b"evil")]);
+            assert!(unzip(zip, &outpath, &UnzipOptions::default()).is_err(), "{} was not rejected", name);
+            assert_nothing_extracted(&dir);
+        }
+
+        let zip = zip_with_files(&[("index.html", b"<html></html>"), ("../evil.js", b"evil")]);
+        let options = UnzipOptions { unsafe_paths: UnsafeEntryPolicy::Skip, ..UnzipOptions::default() };
+        unzip(zip, &outpath, &options).unwrap();
+        assert!(outpath.join("index.html").exists());
+        assert!(!dir.join("evil.js").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Turns an entry of a zip archive into a symlink by setting its unix mode in the central
+    /// directory, since the zip writer only writes regular files and directories
+    fn mark_as_symlink(mut zip_bytes: Vec<u8>, name: &str) -> Vec<u8> {
+        let read_u16 = |bytes: &[u8], at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize;
+        let mut at = 0;
+        while at + 46 <= zip_bytes.len() {
+            if zip_bytes[at..at + 4] == [0x50, 0x4b, 0x01, 0x02] {
+                let name_len = read_u16(&zip_bytes, at + 28);
+                if &zip_bytes[at + 46..at + 46 + name_len] == name.as_bytes() {
+                    zip_bytes[at + 38..at + 42].copy_from_slice(&(0o120777u32 << 16).to_le_bytes());
+                    return zip_bytes;
+                }
+            }
+            at += 1;
+        }
+        panic!("zip archive has no entry {}", name);
+    }
+
+    #[test]
+    fn symlink_entries_are_rejected_or_skipped() {
+        let dir = test_dir();
+        let outpath = dir.join("ui");
+        let zip_with_symlink = || {
+            let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+            zip.start_file("index.html", zip::write::FileOptions::default()).unwrap();
+            zip.write_all(b"<html></html>").unwrap();
+            zip.start_file("passwd", zip::write::FileOptions::default()).unwrap();
+            zip.write_all(b"/etc/passwd").unwrap();
+            mark_as_symlink(zip.finish().unwrap().into_inner(), "passwd")
+        };
+
+        assert!(unzip(zip_with_symlink(), &outpath, &UnzipOptions::default()).is_err());
+        assert_nothing_extracted(&dir);
+
+        let options = UnzipOptions { symlinks: UnsafeEntryPolicy::Skip, ..UnzipOptions::default() };
+        unzip(zip_with_symlink(), &outpath, &options).unwrap();
+        assert!(outpath.join("index.html").exists());
+        assert!(fs::symlink_metadata(outpath.join("passwd")).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn zip_bombs_are_rejected() {
+        let dir = test_dir();
+        let outpath = dir.join("ui");
+
+        let zeros = vec![0; 10 * 1024 * 1024];
+        assert!(unzip(zip_with_files(&[("bomb.js", &zeros)]), &outpath, &UnzipOptions::default()).is_err());
+        assert_nothing_extracted(&dir);
+
+        // below the minimum size, highly compressible files are extracted regardless of their ratio
+        let small_zeros = vec![0; 100 * 1024];
+        unzip(zip_with_files(&[("sparse.bin", &small_zeros)]), &outpath, &UnzipOptions::default()).unwrap();
+        assert_eq!(fs::read(outpath.join("sparse.bin")).unwrap(), small_zeros);
+        fs::remove_dir_all(&outpath).unwrap();
+
+        let too_large = UnzipOptions { max_total_size: 1000, ..UnzipOptions::default() };
+        let content = b"console.log('main')".repeat(30);
+        let zip = zip_with_files(&[("a.js", &content), ("b.js", &content)]);
+        assert!(unzip(zip, &outpath, &too_large).is_err());
+        assert_nothing_extracted(&dir);
+
+        let too_many = UnzipOptions { max_entries: 2, ..UnzipOptions::default() };
+        let zip = zip_with_files(&[("a.js", b"a"), ("b.js", b"b"), ("c.js", b"c")]);
+        assert!(unzip(zip, &outpath, &too_many).is_err());
+        assert_nothing_extracted(&dir);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn webhapp_inspection_shows_roles_dnas_and_zomes() {
+        let webhapp = webhapp_bytes(&happ_bytes(), &ui_zip_bytes());
+        let inspection = block_on(inspect_bundle(Either::A(webhapp))).unwrap();
+
+        assert_eq!(inspection.name, "app");
+        assert_eq!(inspection.description.as_deref(), Some("An app"));
+        let webhapp = inspection.webhapp.unwrap();
+        assert_eq!(webhapp.happ_location, "app.happ");
+        assert_eq!(webhapp.ui_location, "ui.zip");
+
+        assert_eq!(inspection.roles.len(), 1);
+        let role = &inspection.roles[0];
+        assert_eq!(role.name, "role");
+        assert!(matches!(role.provisioning, CellProvisioningStrategy::Create));
+        assert_eq!(role.deferred, Some(false));
+        assert_eq!(role.protected, None);
+        assert_eq!(role.clone_limit, 3);
+
+        // The modifiers of the role override those of the DNA manifest
+        let dna = role.dna.as_ref().unwrap();
+        assert_eq!(dna.name, "dna");
+        assert_eq!(dna.network_seed, "role-seed");
+        assert_eq!(dna.properties, serde_json::json!({ "foo": "bar" }));
+        assert_eq!(dna.origin_time, 1_700_000_000_000_000);
+        assert!(DnaHashB64::from_b64_str(&dna.dna_hash).is_ok());
+
+        let zome_names = |zomes: &[ZomeInspection]| {
+            zomes.iter().map(|zome| zome.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(zome_names(&dna.integrity_zomes), vec!["integrity"]);
+        assert_eq!(zome_names(&dna.coordinator_zomes), vec!["coordinator"]);
+        assert_ne!(dna.integrity_zomes[0].wasm_hash, dna.coordinator_zomes[0].wasm_hash);
+
+        // A plain happ has no webhapp locations but the same roles
+        let happ_inspection = block_on(inspect_bundle(Either::A(happ_bytes()))).unwrap();
+        assert!(happ_inspection.webhapp.is_none());
+        assert_eq!(happ_inspection.roles[0].dna.as_ref().unwrap().dna_hash, dna.dna_hash);
+    }
+
+    #[test]
+    fn bundle_files_are_inspected_like_their_bytes() {
+        let dir = test_dir();
+        let webhapp = webhapp_bytes(&happ_bytes(), &ui_zip_bytes());
+        let webhapp_path = dir.join("app.webhapp");
+        let happ_path = dir.join("app.happ");
+        fs::write(&webhapp_path, &webhapp).unwrap();
+        fs::write(&happ_path, happ_bytes()).unwrap();
+
+        let inspect_file = |path: &Path| block_on(inspect_bundle(Either::B(path.to_string_lossy().to_string()))).unwrap();
+        let from_bytes = block_on(inspect_bundle(Either::A(webhapp))).unwrap();
+        let from_file = inspect_file(&webhapp_path);
+
+        assert_eq!(from_file.name, from_bytes.name);
+        let webhapp_locations = from_file.webhapp.as_ref().unwrap();
+        assert_eq!(webhapp_locations.happ_location, "app.happ");
+        assert_eq!(webhapp_locations.ui_location, "ui.zip");
+        let dna_hash = |inspection: &BundleInspection| inspection.roles[0].dna.as_ref().unwrap().dna_hash.clone();
+        assert_eq!(dna_hash(&from_file), dna_hash(&from_bytes));
+
+        let happ_inspection = inspect_file(&happ_path);
+        assert!(happ_inspection.webhapp.is_none());
+        assert_eq!(dna_hash(&happ_inspection), dna_hash(&from_bytes));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn dna_hashes_follow_network_seed_and_role_modifiers() {
+        let happ = happ_bytes();
+        let compute = |network_seed: Option<&str>, role_modifiers: Option<HashMap<String, DnaModifiersOverride>>| {
+            block_on(compute_dna_hashes(happ.clone(), network_seed.map(String::from), role_modifiers))
+        };
+        let role_dna_hash = |dna_hashes: Vec<RoleDnaHash>| {
+            assert_eq!(dna_hashes.len(), 1);
+            assert_eq!(dna_hashes[0].role_name, "role");
+            dna_hashes[0].dna_hash.clone().unwrap()
+        };
+        let seed_override = |network_seed: &str| {
+            HashMap::from([(
+                "role".to_string(),
+                DnaModifiersOverride {
+                    network_seed: Some(network_seed.to_string()),
+                    properties: None,
+                    origin_time: None,
+                },
+            )])
+        };
+
+        let inspection = block_on(inspect_bundle(Either::A(happ.clone()))).unwrap();
+        let manifest_hash = role_dna_hash(compute(None, None).unwrap());
+        assert_eq!(manifest_hash, inspection.roles[0].dna.as_ref().unwrap().dna_hash);
+
+        let other_seed_hash = role_dna_hash(compute(Some("other-seed"), None).unwrap());
+        assert_ne!(other_seed_hash, manifest_hash);
+        assert_eq!(
+            role_dna_hash(compute(None, Some(seed_override("other-seed"))).unwrap()),
+            other_seed_hash
+        );
+
+        // Role modifiers take precedence over the network seed of the installation
+        assert_eq!(
+            role_dna_hash(compute(Some("other-seed"), Some(seed_override("role-seed"))).unwrap()),
+            manifest_hash
+        );
+
+        let properties_override = HashMap::from([(
+            "role".to_string(),
+            DnaModifiersOverride {
+                network_seed: None,
+                properties: Some(serde_json::json!({ "foo": "baz" })),
+                origin_time: None,
+            },
+        )]);
+        assert_ne!(role_dna_hash(compute(None, Some(properties_override)).unwrap()), manifest_hash);
+
+        let unknown_role = HashMap::from([(
+            "unknown".to_string(),
+            DnaModifiersOverride {
+                network_seed: None,
+                properties: None,
+                origin_time: None,
+            },
+        )]);
+        assert!(compute(None, Some(unknown_role)).is_err());
+    }
+}
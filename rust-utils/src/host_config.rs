@@ -0,0 +1,241 @@
+use serde_json::{Map, Value};
+
+/// Index below the device bundle the host seed is derived at in v3 and flat host config
+/// files without a device_derivation_path field
+const DEFAULT_HOST_SEED_DERIVATION_INDEX: u32 = 1;
+
+/// Index below the device bundle the host seed is derived at in v2 host config files
+/// without a derivation_path field
+const DEFAULT_HOST_SEED_DERIVATION_INDEX_V2: u32 = 3;
+
+/// Layout of a host config json file
+#[napi(string_enum = "lowercase")]
+pub enum HostConfigFormat {
+    /// Fields nested under a top-level "v2" key
+    V2,
+    /// Fields nested under a top-level "v3" key
+    V3,
+    /// v3 fields at the top level of the file
+    Flat,
+}
+
+/// Contents of a host config json file, as far as they are relevant for importing
+/// the host seed into lair
+#[napi(object)]
+pub struct HostConfig {
+    pub format: HostConfigFormat,
+    /// URL safe base64 encoded locked device bundle, regardless of the base64
+    /// variant used in the file
+    pub device_bundle: String,
+    /// Derivation indices leading from the device bundle to the host seed
+    pub device_derivation_path: Vec<u32>,
+    pub revocation_pub_key: Option<String>,
+    pub holoport_id: Option<String>,
+    /// Base64 encoded public key the host seed is expected to have
+    pub initial_host_pub_key: Option<String>,
+    pub registration_code: Option<String>,
+    pub admin_email: Option<String>,
+}
+
+/// Parses a host config json file in the v2, v3 or flat format
+#[napi]
+pub fn parse_host_config(json: String) -> napi::Result<HostConfig> {
+    let value: Value = serde_json::from_str(&json)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to parse host config: {}", e)))?;
+    let object = value
+        .as_object()
+        .ok_or(napi::Error::from_reason("Host config is not a json object"))?;
+
+    if let Some(v3_config) = object.get("v3") {
+        host_config_from_fields(HostConfigFormat::V3, nested_fields(v3_config, "v3")?)
+    } else if let Some(v2_config) = object.get("v2") {
+        host_config_from_fields(HostConfigFormat::V2, nested_fields(v2_config, "v2")?)
+    } else if object.contains_key("device_bundle") {
+        host_config_from_fields(HostConfigFormat::Flat, object)
+    } else {
+        Err(napi::Error::from_reason(
+            "Unknown host config format: expected a top-level v2 or v3 key or a device_bundle field",
+        ))
+    }
+}
+
+fn nested_fields<'a>(value: &'a Value, key: &str) -> napi::Result<&'a Map<String, Value>> {
+    value.as_object().ok_or(napi::Error::from_reason(format!(
+        "The {} key is not of type object",
+        key
+    )))
+}
+
+fn host_config_from_fields(
+    format: HostConfigFormat,
+    fields: &Map<String, Value>,
+) -> napi::Result<HostConfig> {
+    let device_bundle = string_field(fields, "device_bundle")?
+        .ok_or(napi::Error::from_reason("device_bundle field not found"))?;
+
+    // v2 files name the derivation path field differently and use another default index
+    let (derivation_path_field, default_index) = match format {
+        HostConfigFormat::V2 => ("derivation_path", DEFAULT_HOST_SEED_DERIVATION_INDEX_V2),
+        _ => ("device_derivation_path", DEFAULT_HOST_SEED_DERIVATION_INDEX),
+    };
+    let device_derivation_path = match fields.get(derivation_path_field) {
+        Some(path) => parse_device_derivation_path(path)?,
+        None => vec![default_index],
+    };
+
+    let holoport_id = match format {
+        // v2 files hold the base36 encoded holoport id as encoded_public_key
+        HostConfigFormat::V2 => string_field(fields, "encoded_public_key")?,
+        _ => string_field(fields, "holoport_id")?,
+    };
+
+    let admin_email = fields
+        .get("settings")
+        .and_then(|settings| settings.get("admin"))
+        .and_then(|admin| admin.get("email"))
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    Ok(HostConfig {
+        format,
+        device_bundle: normalize_device_bundle(&device_bundle)?,
+        device_derivation_path,
+        revocation_pub_key: string_field(fields, "revocation_pub_key")?,
+        holoport_id,
+        initial_host_pub_key: string_field(fields, "initial_host_pub_key")?,
+        registration_code: string_field(fields, "registration_code")?,
+        admin_email,
+    })
+}
+
+fn string_field(fields: &Map<String, Value>, name: &str) -> napi::Result<Option<String>> {
+    match fields.get(name) {
+        Some(value) => {
+            value
+                .as_str()
+                .map(|value| Some(value.to_string()))
+                .ok_or(napi::Error::from_reason(format!(
+                    "{} value is not of type string",
+                    name
+                )))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Re-encodes a device bundle in either base64 variant, with or without padding,
+/// as URL safe base64 without padding
+fn normalize_device_bundle(device_bundle: &str) -> napi::Result<String> {
+    let device_bundle = device_bundle.trim().trim_end_matches('=');
+    let config = if device_bundle.contains(['+', '/']) {
+        base64::STANDARD_NO_PAD
+    } else {
+        base64::URL_SAFE_NO_PAD
+    };
+    let locked_bundle = base64::decode_config(device_bundle, config).map_err(|e| {
+        napi::Error::from_reason(format!("Failed to decode device bundle: {:?}", e))
+    })?;
+
+    Ok(base64::encode_config(
+        locked_bundle,
+        base64::URL_SAFE_NO_PAD,
+    ))
+}
+
+/// Parses the device derivation path field of a host config, which is either a single
+/// index, e.g. 2 or "2", or a path of indices, e.g. "m/1/2" or [1, 2]
+fn parse_device_derivation_path(path: &Value) -> napi::Result<Vec<u32>> {
+    let invalid_path = || {
+        napi::Error::from_reason(format!(
+            "device_derivation_path value {} is not a valid derivation path",
+            path
+        ))
+    };
+    let index = |value: &Value| {
+        value
+            .as_u64()
+            .and_then(|index| u32::try_from(index).ok())
+            .ok_or_else(invalid_path)
+    };
+
+    let derivation_path = match path {
+        Value::Number(_) => vec![index(path)?],
+        Value::Array(indices) => indices.iter().map(index).collect::<napi::Result<_>>()?,
+        Value::String(path_string) => path_string
+            .trim()
+            .trim_start_matches("m/")
+            .split('/')
+            .map(|index| index.trim().parse::<u32>().map_err(|_| invalid_path()))
+            .collect::<napi::Result<_>>()?,
+        _ => return Err(invalid_path()),
+    };
+
+    if derivation_path.is_empty() {
+        return Err(invalid_path());
+    }
+
+    Ok(derivation_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAT_HOST_CONFIG: &str = include_str!("../../test_seeds.json");
+
+    #[test]
+    fn flat_and_v3_host_configs_parse_to_the_same_fields() {
+        let flat = parse_host_config(FLAT_HOST_CONFIG.to_string()).unwrap();
+        let flat_value: Value = serde_json::from_str(FLAT_HOST_CONFIG).unwrap();
+        let v3 = parse_host_config(serde_json::json!({ "v3": flat_value }).to_string()).unwrap();
+
+        assert!(matches!(flat.format, HostConfigFormat::Flat));
+        assert!(matches!(v3.format, HostConfigFormat::V3));
+        for config in [&flat, &v3] {
+            assert_eq!(config.device_derivation_path, vec![2]);
+            assert_eq!(
+                config.holoport_id.as_deref(),
+                Some("s97ht0w3jbi2960anz7tciany8300izgini7utvf021dgz4b1")
+            );
+            assert_eq!(
+                config.revocation_pub_key.as_deref(),
+                Some("CXPgTFVxfhvQQpd8RaLIAZYRKYqsOmoGzv7AVm+dM4Y")
+            );
+            assert_eq!(
+                config.registration_code.as_deref(),
+                Some("registration-code")
+            );
+            assert_eq!(config.admin_email.as_deref(), Some("joel@holo.host"));
+        }
+
+        // The standard base64 bundle of the file is re-encoded as URL safe base64
+        let device_bundle = flat_value["device_bundle"].as_str().unwrap();
+        assert_eq!(
+            base64::decode_config(&flat.device_bundle, base64::URL_SAFE_NO_PAD).unwrap(),
+            base64::decode(device_bundle).unwrap()
+        );
+        assert_eq!(flat.device_bundle, v3.device_bundle);
+    }
+
+    #[test]
+    fn v2_host_config_uses_v2_field_names() {
+        let v2 = parse_host_config(
+            serde_json::json!({
+                "v2": {
+                    "device_bundle": "k6VoY3NiMJA",
+                    "encoded_public_key": "holoport-id",
+                    "registration_code": "registration-code",
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        assert!(matches!(v2.format, HostConfigFormat::V2));
+        assert_eq!(v2.device_derivation_path, vec![3]);
+        assert_eq!(v2.holoport_id.as_deref(), Some("holoport-id"));
+        assert_eq!(v2.initial_host_pub_key, None);
+
+        assert!(parse_host_config(r#"{ "v1": {} }"#.to_string()).is_err());
+    }
+}
@@ -1,104 +1,643 @@
-use hc_seed_bundle::UnlockedSeedBundle;
+use hc_seed_bundle::dependencies::futures::{stream, FutureExt, StreamExt, TryStreamExt};
+use hc_seed_bundle::dependencies::{one_err, rmpv, sodoken};
+use hc_seed_bundle::{PwHashLimits, UnlockedSeedBundle};
+use holo_hash::{AgentPubKey, AgentPubKeyB64};
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::SystemTime;
 
+use crate::launcher_lair_client::{unlock_device_bundle, unlock_seed_bundle, SeedBundleSecret};
+use crate::passphrase::Passphrase;
+
+/// Index of the revocation seed below the root seed as per DeepKey conventions
+pub const DEFAULT_REVOCATION_SEED_INDEX: u32 = 0;
+/// Index of the device seeds seed below the root seed as per DeepKey conventions
+pub const DEFAULT_DEVICE_SEEDS_SEED_INDEX: u32 = 1;
+
+/// Upper bound for the argon2 memory of the seed encryptions running at the same time
+const MAX_CONCURRENT_PW_HASH_MEMORY: usize = 512 * 1024 * 1024;
+/// Upper bound for the number of seed encryptions running at the same time
+const MAX_CONCURRENT_SEED_ENCRYPTIONS: usize = 4;
+
+/// Version of the key file format written by this library. Key files without a
+/// format version predate versioning and are migrated when parsed.
+pub const KEY_FILE_FORMAT_VERSION: u32 = 1;
+
 #[napi(object)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedSeed {
+    /// Base64 (url safe, no padding) encoded locked seed bundle
+    pub bundle: String,
+    /// Types of the ciphers that can unlock the seed bundle, e.g. "PwHash" or "SecurityQuestions"
+    pub ciphers: Vec<String>,
+    /// Base64 encoded ed25519 public key of the seed. Only missing for seeds migrated
+    /// from key files that predate public keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pub_key: Option<String>,
+    /// Derivation indices leading from the root seed to this seed, empty for the root seed
+    pub derivation_path: Vec<u32>,
+    /// Short checksum of the public key to tell keys apart at a glance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// Weakest argon2 limits among the passphrase based ciphers of the seed bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pw_hash_limits: Option<PwHashLimitsInfo>,
+}
+
+/// Argon2 limit presets for encrypting seeds. Stronger limits make brute forcing
+/// the passphrase more expensive but also take longer to lock and unlock seeds.
+#[napi(string_enum = "lowercase")]
+pub enum PwHashLimitsPreset {
+    Interactive,
+    Moderate,
+    Sensitive,
+}
+
+impl From<PwHashLimitsPreset> for PwHashLimits {
+    fn from(preset: PwHashLimitsPreset) -> Self {
+        match preset {
+            PwHashLimitsPreset::Interactive => PwHashLimits::Interactive,
+            PwHashLimitsPreset::Moderate => PwHashLimits::Moderate,
+            PwHashLimitsPreset::Sensitive => PwHashLimits::Sensitive,
+        }
+    }
+}
+
+/// Argon2 limits a seed bundle has been encrypted with
+#[napi(object)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PwHashLimitsInfo {
+    /// Name of the matching preset, e.g. "moderate", or "custom" if no preset matches
+    pub preset: String,
+    pub mem_limit: u32,
+    pub ops_limit: u32,
+    /// Whether the limits are weaker than the default "moderate" preset
+    pub weak: bool,
+}
+
+impl PwHashLimitsInfo {
+    fn new(mem_limit: u32, ops_limit: u32) -> Self {
+        let presets = [
+            (PwHashLimits::Minimum, "minimum"),
+            (PwHashLimits::Interactive, "interactive"),
+            (PwHashLimits::Moderate, "moderate"),
+            (PwHashLimits::Sensitive, "sensitive"),
+        ];
+        let preset = presets
+            .iter()
+            .find(|(limits, _)| {
+                limits.as_mem_limit() == mem_limit && limits.as_ops_limit() == ops_limit
+            })
+            .map_or("custom", |(_, name)| name);
+
+        PwHashLimitsInfo {
+            preset: preset.to_string(),
+            mem_limit,
+            ops_limit,
+            weak: mem_limit < PwHashLimits::Moderate.as_mem_limit()
+                || ops_limit < PwHashLimits::Moderate.as_ops_limit(),
+        }
+    }
+}
+
+/// Reads the argon2 limits of all ciphers from the msgpack encoded locked seed bundle
+/// and returns the weakest ones
+fn read_pw_hash_limits(locked_bundle: &[u8]) -> napi::Result<PwHashLimitsInfo> {
+    let invalid_bundle =
+        |reason: &str| napi::Error::from_reason(format!("Invalid seed bundle: {}", reason));
+
+    let bundle = rmpv::decode::read_value(&mut &locked_bundle[..])
+        .map_err(|e| invalid_bundle(&e.to_string()))?;
+    // A seed bundle is encoded as ["hcsb0", [cipher, ...], app_data] where each
+    // cipher starts with [cipher_type, salt, mem_limit, ops_limit, ...]
+    let ciphers = bundle
+        .as_array()
+        .and_then(|bundle| bundle.get(1))
+        .and_then(|ciphers| ciphers.as_array())
+        .ok_or_else(|| invalid_bundle("cipher list not found"))?;
+
+    ciphers
+        .iter()
+        .map(|cipher| {
+            let limit = |index: usize| {
+                cipher
+                    .as_array()
+                    .and_then(|cipher| cipher.get(index))
+                    .and_then(|limit| limit.as_u64())
+                    .and_then(|limit| u32::try_from(limit).ok())
+                    .ok_or_else(|| invalid_bundle("cipher without argon2 limits"))
+            };
+            Ok(PwHashLimitsInfo::new(limit(2)?, limit(3)?))
+        })
+        .collect::<napi::Result<Vec<PwHashLimitsInfo>>>()?
+        .into_iter()
+        .min_by_key(|info| (info.mem_limit, info.ops_limit))
+        .ok_or_else(|| invalid_bundle("no ciphers found"))
+}
+
+/// Returns the argon2 limits of a base64 encoded locked seed bundle, e.g. to warn
+/// the user before unlocking a seed that has been encrypted with weak limits
+#[napi]
+pub fn get_pw_hash_limits(locked_bundle: String) -> napi::Result<PwHashLimitsInfo> {
+    let locked_bundle = base64::decode_config(locked_bundle.trim(), base64::URL_SAFE_NO_PAD)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode seed bundle: {}", e)))?;
+    read_pw_hash_limits(&locked_bundle)
+}
+
+/// Describes how the seeds of a key file are encrypted
+#[napi(object)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyFileKdf {
+    /// Hash applied to passphrases before the key derivation
+    pub passphrase_prehash: String,
+    /// Password hashing algorithm deriving the encryption key from the passphrase
+    pub algorithm: String,
+    /// Cipher the seeds are encrypted with
+    pub cipher: String,
+    /// Format of the locked seed bundles
+    pub seed_bundle_format: String,
+}
+
+impl Default for KeyFileKdf {
+    fn default() -> Self {
+        // As implemented by hc_seed_bundle
+        Self {
+            passphrase_prehash: "blake2b-512".to_string(),
+            algorithm: "argon2id".to_string(),
+            cipher: "xchacha20poly1305-secretstream".to_string(),
+            seed_bundle_format: "hcsb0".to_string(),
+        }
+    }
+}
+
+/// Versioned key file. Together with their derivation paths, the seeds form the
+/// derivation tree below the root seed.
+#[napi(object)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct KeyFile {
-    pub root_seed: String,
-    pub revocation_seed: String,
-    pub device_seeds_seed: String,
-    pub revocation_key_0: String,
-    pub device_seed_0: String,
+    pub format_version: u32,
+    pub kdf: KeyFileKdf,
+    pub root_seed: EncryptedSeed,
+    pub revocation_seed: EncryptedSeed,
+    pub device_seeds_seed: EncryptedSeed,
+    /// Revocation keys derived from the revocation seed, keyed by their derivation index
+    pub revocation_keys: BTreeMap<String, EncryptedSeed>,
+    /// Device seeds derived from the device seeds seed, keyed by their derivation index
+    pub device_seeds: BTreeMap<String, EncryptedSeed>,
+    /// Creation time in seconds since the Unix epoch
     pub timestamp: f64,
 }
 
+/// Describes which seeds to derive from the root seed.
+///
+/// Revocation and device seed indices are taken from the explicit index lists if
+/// provided, otherwise the first `count` indices are used. Without a spec, a single
+/// revocation key and device seed are derived at index 0.
+#[napi(object)]
+pub struct DerivationSpec {
+    /// Defaults to 0
+    pub revocation_seed_index: Option<u32>,
+    /// Defaults to 1
+    pub device_seeds_seed_index: Option<u32>,
+    pub revocation_key_count: Option<u32>,
+    pub revocation_key_indices: Option<Vec<u32>>,
+    pub device_seed_count: Option<u32>,
+    pub device_seed_indices: Option<Vec<u32>>,
+}
+
+struct DerivationIndices {
+    revocation_seed_index: u32,
+    device_seeds_seed_index: u32,
+    revocation_key_indices: Vec<u32>,
+    device_seed_indices: Vec<u32>,
+}
+
+fn derivation_indices(spec: Option<DerivationSpec>) -> napi::Result<DerivationIndices> {
+    let spec = spec.unwrap_or(DerivationSpec {
+        revocation_seed_index: None,
+        device_seeds_seed_index: None,
+        revocation_key_count: None,
+        revocation_key_indices: None,
+        device_seed_count: None,
+        device_seed_indices: None,
+    });
+
+    let indices = |name: &str, indices: Option<Vec<u32>>, count: Option<u32>| {
+        let indices = match (indices, count) {
+            (Some(indices), _) => indices,
+            (None, Some(count)) => (0..count).collect(),
+            (None, None) => vec![0],
+        };
+        if indices.is_empty() {
+            return Err(napi::Error::from_reason(format!(
+                "At least one {} needs to be derived",
+                name
+            )));
+        }
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = indices.iter().find(|i| !seen.insert(**i)) {
+            return Err(napi::Error::from_reason(format!(
+                "Duplicate {} index {}",
+                name, duplicate
+            )));
+        }
+        Ok(indices)
+    };
+
+    let revocation_seed_index = spec
+        .revocation_seed_index
+        .unwrap_or(DEFAULT_REVOCATION_SEED_INDEX);
+    let device_seeds_seed_index = spec
+        .device_seeds_seed_index
+        .unwrap_or(DEFAULT_DEVICE_SEEDS_SEED_INDEX);
+    if revocation_seed_index == device_seeds_seed_index {
+        return Err(napi::Error::from_reason(format!(
+            "Revocation seed and device seeds seed cannot both be derived at index {}",
+            revocation_seed_index
+        )));
+    }
+
+    Ok(DerivationIndices {
+        revocation_seed_index,
+        device_seeds_seed_index,
+        revocation_key_indices: indices(
+            "revocation key",
+            spec.revocation_key_indices,
+            spec.revocation_key_count,
+        )?,
+        device_seed_indices: indices(
+            "device seed",
+            spec.device_seed_indices,
+            spec.device_seed_count,
+        )?,
+    })
+}
+
+/// Three security questions and their answers
+#[napi(object, object_to_js = false)]
+pub struct SecurityQuestions {
+    pub questions: Vec<String>,
+    pub answers: Vec<Passphrase>,
+}
+
+/// Passphrases for individual seeds. Seeds without a passphrase in here are
+/// encrypted with the default passphrase. The revocation key and device seed
+/// passphrases apply to all derived revocation keys and device seeds respectively.
+#[napi(object, object_to_js = false)]
+pub struct SeedPassphrases {
+    pub root_seed: Option<Passphrase>,
+    pub revocation_seed: Option<Passphrase>,
+    pub device_seeds_seed: Option<Passphrase>,
+    pub revocation_key: Option<Passphrase>,
+    pub device_seed: Option<Passphrase>,
+}
+
+#[napi(object, object_to_js = false)]
+pub struct KeyGenerationOptions {
+    pub derivation: Option<DerivationSpec>,
+    pub passphrases: Option<SeedPassphrases>,
+    /// If provided, every seed can additionally be unlocked by answering these questions
+    pub security_questions: Option<SecurityQuestions>,
+    /// Defaults to the "moderate" preset
+    pub pw_hash_limits: Option<PwHashLimitsPreset>,
+}
+
+type SecurityQuestionLists = (
+    (String, String, String),
+    (Passphrase, Passphrase, Passphrase),
+);
+
+fn security_question_lists(
+    security_questions: SecurityQuestions,
+) -> napi::Result<SecurityQuestionLists> {
+    let SecurityQuestions { questions, answers } = security_questions;
+    let questions: [String; 3] = questions.try_into().map_err(|q: Vec<String>| {
+        napi::Error::from_reason(format!(
+            "Expected exactly 3 security questions but got {}",
+            q.len()
+        ))
+    })?;
+    let answers: [Passphrase; 3] = answers.try_into().map_err(|a: Vec<Passphrase>| {
+        napi::Error::from_reason(format!(
+            "Expected exactly 3 security answers but got {}",
+            a.len()
+        ))
+    })?;
+    if answers.iter().any(|a| a.expose_secret().trim().is_empty()) {
+        return Err(napi::Error::from_reason(
+            "Security answers must not be empty",
+        ));
+    }
+
+    let [q1, q2, q3] = questions;
+    let [a1, a2, a3] = answers;
+    Ok(((q1, q2, q3), (a1, a2, a3)))
+}
+
+/// Hex encoded 4 byte blake2b hash of the given bytes, e.g. "a1b2-c3d4"
+fn short_checksum(bytes: &[u8]) -> napi::Result<String> {
+    let hash = holo_hash::encode::blake2b_n(bytes, 4)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to compute checksum: {}", e)))?;
+    let hex: Vec<String> = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}-{}", hex[..2].concat(), hex[2..].concat()))
+}
+
+/// Encrypts the seed with a pwhash cipher for the given passphrase and, if provided,
+/// a security questions cipher, and base64 encodes the resulting locked seed bundle
+async fn lock_seed(
+    seed: &UnlockedSeedBundle,
+    passphrase: &Passphrase,
+    security_questions: Option<&SecurityQuestionLists>,
+    derivation_path: Vec<u32>,
+    pw_hash_limits: PwHashLimits,
+    seed_name: &str,
+) -> napi::Result<EncryptedSeed> {
+    let mut ciphers = vec!["PwHash".to_string()];
+    let passphrase = passphrase.to_buf_read()?;
+    let security_questions = match security_questions {
+        Some((questions, (a1, a2, a3))) => Some((
+            questions.clone(),
+            (a1.to_buf_read()?, a2.to_buf_read()?, a3.to_buf_read()?),
+        )),
+        None => None,
+    };
+
+    // The ciphers pick up the limits when they are added to the builder
+    let cipher_builder = pw_hash_limits.with_exec(|| {
+        let mut cipher_builder = seed.lock().add_pwhash_cipher(passphrase);
+
+        if let Some((questions, answers)) = security_questions {
+            cipher_builder = cipher_builder.add_security_question_cipher(questions, answers);
+            ciphers.push("SecurityQuestions".to_string());
+        }
+
+        cipher_builder
+    });
+
+    let seed_encrypted = cipher_builder
+        .lock()
+        .await
+        .map_err(|e| napi::Error::from_reason(format!("Failed to encrypt {}: {}", seed_name, e)))?;
+
+    let sign_pub_key = seed.get_sign_pub_key().read_lock().to_vec();
+    let pw_hash_limits = read_pw_hash_limits(&seed_encrypted)?;
+
+    Ok(EncryptedSeed {
+        bundle: base64::encode_config(seed_encrypted, base64::URL_SAFE_NO_PAD),
+        ciphers,
+        checksum: Some(short_checksum(&sign_pub_key)?),
+        pub_key: Some(AgentPubKeyB64::from(AgentPubKey::from_raw_32(sign_pub_key)).to_string()),
+        derivation_path,
+        pw_hash_limits: Some(pw_hash_limits),
+    })
+}
+
+/// Progress of the seed encryptions while generating initial seeds
+#[napi(object)]
+pub struct SeedEncryptionProgress {
+    /// Name of the seed, e.g. "root seed" or "device seed 0"
+    pub seed_name: String,
+    pub derivation_path: Vec<u32>,
+    /// Whether the encryption of the seed has just started or finished
+    pub finished: bool,
+    /// Number of seeds encrypted so far
+    pub encrypted_count: u32,
+    pub total_count: u32,
+}
+
+/// A derived seed waiting to be encrypted
+struct SeedToLock {
+    seed: UnlockedSeedBundle,
+    passphrase: Passphrase,
+    derivation_path: Vec<u32>,
+    name: String,
+}
 
-/// Generates root seed, revocation key and device seed
+/// Generates root seed, revocation seed and device seeds seed as well as the
+/// revocation keys and device seeds described by the derivation spec
 ///
-/// Use a single passphrase for the whole file for starters
+/// All seeds are encrypted with the given passphrase unless a separate passphrase
+/// is specified for them in the options. A few seeds are encrypted concurrently and
+/// the optional progress callback is called whenever the encryption of a seed
+/// starts or finishes.
 #[napi]
-pub async fn generate_initial_seeds(passphrase: String) -> napi::Result<KeyFile> {
-    // Generate, encrypt and base64 encode root seed
+pub async fn generate_initial_seeds(
+    passphrase: Passphrase,
+    options: Option<KeyGenerationOptions>,
+    progress: Option<SeedEncryptionProgressCallback>,
+) -> napi::Result<KeyFile> {
     let root_seed = UnlockedSeedBundle::new_random()
         .await
         .map_err(|e| napi::Error::from_reason(format!("Failed to generate random seed: {}", e)))?;
 
-    let root_seed_encrypted = root_seed
-        .lock()
-        .add_pwhash_cipher(passphrase.as_bytes().to_owned())
-        .lock()
-        .await
-        .map_err(|e| napi::Error::from_reason(format!("Failed to encrypt root seed: {}", e)))?;
+    key_file_from_root_seed(root_seed, passphrase, options, |seed_progress| {
+        report_progress_to(&progress, seed_progress)
+    })
+    .await
+}
 
-    let root_seed_b64 = base64::encode_config(root_seed_encrypted, base64::URL_SAFE_NO_PAD);
+/// Callback reporting the encryption progress of seeds. It is called node style with an
+/// error as first argument, which is always null.
+pub type SeedEncryptionProgressCallback =
+    ThreadsafeFunction<SeedEncryptionProgress, ErrorStrategy::CalleeHandled>;
 
-    // Derive, encrypt and base64 encode revocation seed
-    let revocation_seed = root_seed.derive(0).await.map_err(|e| {
-        napi::Error::from_reason(format!("Failed to derive revocation seed: {}", e))
-    })?;
+/// Calls the progress callback without waiting for it to return. Of the ways to call a
+/// threadsafe function, only call_async catches an exception thrown by the callback instead
+/// of raising it as uncaught exception in the main process, so that a bug in reporting the
+/// progress cannot abort the key generation. Polling the call once queues it, the result
+/// is not waited for.
+fn report_progress_to(
+    progress: &Option<SeedEncryptionProgressCallback>,
+    seed_progress: SeedEncryptionProgress,
+) {
+    if let Some(progress) = progress {
+        let _ = progress
+            .call_async::<serde_json::Value>(Ok(seed_progress))
+            .now_or_never();
+    }
+}
 
-    let revocation_seed_encrypted = revocation_seed
-        .lock()
-        .add_pwhash_cipher(passphrase.as_bytes().to_owned())
-        .lock()
+/// Derives all seeds of a KeyFile from the given root seed and encrypts them
+pub(crate) async fn key_file_from_root_seed(
+    root_seed: UnlockedSeedBundle,
+    passphrase: Passphrase,
+    options: Option<KeyGenerationOptions>,
+    report_progress: impl Fn(SeedEncryptionProgress) + Sync,
+) -> napi::Result<KeyFile> {
+    let KeyGenerationOptions {
+        derivation,
+        passphrases,
+        security_questions,
+        pw_hash_limits,
+    } = options.unwrap_or(KeyGenerationOptions {
+        derivation: None,
+        passphrases: None,
+        security_questions: None,
+        pw_hash_limits: None,
+    });
+    let pw_hash_limits = pw_hash_limits.map_or(PwHashLimits::Moderate, PwHashLimits::from);
+    let derivation = derivation_indices(derivation)?;
+    let passphrases = passphrases.unwrap_or(SeedPassphrases {
+        root_seed: None,
+        revocation_seed: None,
+        device_seeds_seed: None,
+        revocation_key: None,
+        device_seed: None,
+    });
+    let security_questions = security_questions
+        .map(security_question_lists)
+        .transpose()?;
+    let security_questions = security_questions.as_ref();
+    let passphrase_for = |seed_passphrase: &Option<Passphrase>| {
+        seed_passphrase.clone().unwrap_or(passphrase.clone())
+    };
+
+    // Derive revocation seed
+    let revocation_seed = root_seed
+        .derive(derivation.revocation_seed_index)
         .await
         .map_err(|e| {
-            napi::Error::from_reason(format!("Failed to encrypt revocation seed: {}", e))
+            napi::Error::from_reason(format!("Failed to derive revocation seed: {}", e))
         })?;
 
-    let revocation_seed_b64 =
-        base64::encode_config(revocation_seed_encrypted, base64::URL_SAFE_NO_PAD);
-
-    // Derive, encrypt and base64 encode device seeds seed
-    let device_seeds_seed = root_seed.derive(1).await.map_err(|e| {
-        napi::Error::from_reason(format!("Failed to derive device seeds seed: {}", e))
-    })?;
-
-    let device_seeds_seed_encrypted = device_seeds_seed
-        .lock()
-        .add_pwhash_cipher(passphrase.as_bytes().to_owned())
-        .lock()
+    // Derive device seeds seed
+    let device_seeds_seed = root_seed
+        .derive(derivation.device_seeds_seed_index)
         .await
         .map_err(|e| {
-            napi::Error::from_reason(format!("Failed to encrypt device seeds seed: {}", e))
+            napi::Error::from_reason(format!("Failed to derive device seeds seed: {}", e))
         })?;
 
-    let device_seeds_seed_b64 =
-        base64::encode_config(device_seeds_seed_encrypted, base64::URL_SAFE_NO_PAD);
+    // Derive revocation keys
+    let mut revocation_keys = Vec::new();
+    for index in &derivation.revocation_key_indices {
+        let revocation_key = revocation_seed.derive(*index).await.map_err(|e| {
+            napi::Error::from_reason(format!("Failed to derive revocation key {}: {}", index, e))
+        })?;
 
-    // Derive, encrypt and base64 encode revocation key and device seed for first device
-    let revocation_key_0 = revocation_seed
-        .derive(0)
-        .await
-        .map_err(|e| napi::Error::from_reason(format!("Failed to derive revocation key: {}", e)))?;
+        revocation_keys.push(SeedToLock {
+            seed: revocation_key,
+            passphrase: passphrase_for(&passphrases.revocation_key),
+            derivation_path: vec![derivation.revocation_seed_index, *index],
+            name: format!("revocation key {}", index),
+        });
+    }
 
-    let revocation_key_0_encrypted = revocation_key_0
-        .lock()
-        .add_pwhash_cipher(passphrase.as_bytes().to_owned())
-        .lock()
-        .await
-        .map_err(|e| {
-            napi::Error::from_reason(format!("Failed to encrypt revocation key: {}", e))
+    // Derive device seeds
+    let mut device_seeds = Vec::new();
+    for index in &derivation.device_seed_indices {
+        let device_seed = device_seeds_seed.derive(*index).await.map_err(|e| {
+            napi::Error::from_reason(format!("Failed to derive device seed {}: {}", index, e))
         })?;
 
-    let revocation_key_0_b64 =
-        base64::encode_config(revocation_key_0_encrypted, base64::URL_SAFE_NO_PAD);
+        device_seeds.push(SeedToLock {
+            seed: device_seed,
+            passphrase: passphrase_for(&passphrases.device_seed),
+            derivation_path: vec![derivation.device_seeds_seed_index, *index],
+            name: format!("device seed {}", index),
+        });
+    }
 
-    let device_seed_0 = device_seeds_seed
-        .derive(0)
-        .await
-        .map_err(|e| napi::Error::from_reason(format!("Failed to derive device seed: {}", e)))?;
+    let seeds_to_lock: Vec<SeedToLock> = [
+        SeedToLock {
+            seed: root_seed,
+            passphrase: passphrase_for(&passphrases.root_seed),
+            derivation_path: vec![],
+            name: "root seed".to_string(),
+        },
+        SeedToLock {
+            seed: revocation_seed,
+            passphrase: passphrase_for(&passphrases.revocation_seed),
+            derivation_path: vec![derivation.revocation_seed_index],
+            name: "revocation seed".to_string(),
+        },
+        SeedToLock {
+            seed: device_seeds_seed,
+            passphrase: passphrase_for(&passphrases.device_seeds_seed),
+            derivation_path: vec![derivation.device_seeds_seed_index],
+            name: "device seeds seed".to_string(),
+        },
+    ]
+    .into_iter()
+    .chain(revocation_keys)
+    .chain(device_seeds)
+    .collect();
 
-    let device_seed_0_encrypted = device_seed_0
-        .lock()
-        .add_pwhash_cipher(passphrase.as_bytes().to_owned())
-        .lock()
-        .await
-        .map_err(|e| {
-            napi::Error::from_reason(format!("Failed to encrypt device seed: {}", e))
-        })?;
+    // The argon2 hashing of each encryption runs on tokio's blocking thread pool,
+    // so the independent encryptions can run concurrently. Each hashing allocates
+    // the argon2 memory limit though, so only as many run at once as fit into
+    // MAX_CONCURRENT_PW_HASH_MEMORY.
+    let max_concurrent_encryptions = (MAX_CONCURRENT_PW_HASH_MEMORY
+        / pw_hash_limits.as_mem_limit() as usize)
+        .clamp(1, MAX_CONCURRENT_SEED_ENCRYPTIONS);
+    let total_count = seeds_to_lock.len() as u32;
+    // Only updated once an encryption has completed, in between polling the encryptions
+    let encrypted_count = AtomicU32::new(0);
+    let report_seed_progress = |seed: &SeedToLock, finished: bool| {
+        report_progress(SeedEncryptionProgress {
+            seed_name: seed.name.clone(),
+            derivation_path: seed.derivation_path.clone(),
+            finished,
+            encrypted_count: encrypted_count.load(Ordering::SeqCst),
+            total_count,
+        })
+    };
+
+    // Streams positions rather than references to the seeds: a closure taking a reference
+    // is not general enough over its lifetime for the future of the napi function to be Send
+    let mut encryptions = stream::iter(0..seeds_to_lock.len())
+        .map(|position| {
+            let seed = &seeds_to_lock[position];
+            let report_seed_progress = &report_seed_progress;
+            async move {
+                report_seed_progress(seed, false);
+                let encrypted_seed = lock_seed(
+                    &seed.seed,
+                    &seed.passphrase,
+                    security_questions,
+                    seed.derivation_path.clone(),
+                    pw_hash_limits,
+                    &seed.name,
+                )
+                .await?;
+                Ok::<(usize, EncryptedSeed), napi::Error>((position, encrypted_seed))
+            }
+        })
+        .buffer_unordered(max_concurrent_encryptions);
 
-    let device_seed_0_b64 =
-        base64::encode_config(device_seed_0_encrypted, base64::URL_SAFE_NO_PAD);
+    let mut encrypted_seeds: Vec<Option<EncryptedSeed>> = vec![None; seeds_to_lock.len()];
+    while let Some((position, encrypted_seed)) = encryptions.try_next().await? {
+        encrypted_count.fetch_add(1, Ordering::SeqCst);
+        report_seed_progress(&seeds_to_lock[position], true);
+        encrypted_seeds[position] = Some(encrypted_seed);
+    }
+    let mut encrypted_seeds = encrypted_seeds.into_iter();
+
+    let mut next_encrypted_seed = || {
+        encrypted_seeds
+            .next()
+            .flatten()
+            .ok_or(napi::Error::from_reason(
+                "Missing encrypted seed after encrypting all seeds",
+            ))
+    };
+    let root_seed_encrypted = next_encrypted_seed()?;
+    let revocation_seed_encrypted = next_encrypted_seed()?;
+    let device_seeds_seed_encrypted = next_encrypted_seed()?;
+    let mut revocation_keys = BTreeMap::new();
+    for index in derivation.revocation_key_indices {
+        revocation_keys.insert(index.to_string(), next_encrypted_seed()?);
+    }
+    let mut device_seeds = BTreeMap::new();
+    for index in derivation.device_seed_indices {
+        device_seeds.insert(index.to_string(), next_encrypted_seed()?);
+    }
 
     let timestamp = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -107,11 +646,1189 @@ pub async fn generate_initial_seeds(passphrase: String) -> napi::Result<KeyFile>
         })?;
 
     Ok(KeyFile {
-        root_seed: root_seed_b64,
-        revocation_seed: revocation_seed_b64,
-        device_seeds_seed: device_seeds_seed_b64,
-        revocation_key_0: revocation_key_0_b64,
-        device_seed_0: device_seed_0_b64,
+        format_version: KEY_FILE_FORMAT_VERSION,
+        kdf: KeyFileKdf::default(),
+        root_seed: root_seed_encrypted,
+        revocation_seed: revocation_seed_encrypted,
+        device_seeds_seed: device_seeds_seed_encrypted,
+        revocation_keys,
+        device_seeds,
         timestamp: timestamp.as_secs_f64(),
     })
 }
+
+pub(crate) fn agent_pub_key_b64(seed: &UnlockedSeedBundle) -> String {
+    AgentPubKeyB64::from(AgentPubKey::from_raw_32(
+        seed.get_sign_pub_key().read_lock().to_vec(),
+    ))
+    .to_string()
+}
+
+/// A locked seed bundle looked up in a KeyFile
+pub(crate) struct KeyFileSeed {
+    pub bundle: String,
+    /// Only known if the key file stores derivation paths
+    pub derivation_path: Option<Vec<u32>>,
+    /// Only known if the key file stores public keys
+    pub pub_key: Option<String>,
+    pub checksum: Option<String>,
+}
+
+impl From<&EncryptedSeed> for KeyFileSeed {
+    fn from(seed: &EncryptedSeed) -> Self {
+        KeyFileSeed {
+            bundle: seed.bundle.clone(),
+            derivation_path: Some(seed.derivation_path.clone()),
+            pub_key: seed.pub_key.clone(),
+            checksum: seed.checksum.clone(),
+        }
+    }
+}
+
+/// Extracts a locked seed bundle from either the JSON of a KeyFile or returns the
+/// input as is if it is not a JSON object, i.e. a base64 encoded locked seed bundle.
+///
+/// The seed is looked up in the key file at the first of the given field paths that exists.
+pub(crate) fn seed_bundle_from_key_file(
+    key_file_json_or_seed_b64: &str,
+    field_paths: &[&[&str]],
+) -> napi::Result<KeyFileSeed> {
+    let key_file = match serde_json::from_str::<serde_json::Value>(key_file_json_or_seed_b64) {
+        Ok(key_file @ serde_json::Value::Object(_)) => key_file,
+        _ => {
+            return Ok(KeyFileSeed {
+                bundle: key_file_json_or_seed_b64.trim().to_string(),
+                derivation_path: None,
+                pub_key: None,
+                checksum: None,
+            })
+        }
+    };
+
+    let (field_path, seed) = field_paths
+        .iter()
+        .find_map(|field_path| {
+            field_path
+                .iter()
+                .try_fold(&key_file, |value, field| value.get(field))
+                .map(|seed| (field_path.join("."), seed))
+        })
+        .ok_or_else(|| {
+            let field_paths: Vec<String> = field_paths.iter().map(|p| p.join(".")).collect();
+            napi::Error::from_reason(format!(
+                "None of the fields [{}] found in the key file",
+                field_paths.join(", ")
+            ))
+        })?;
+
+    // The seed is either stored as an EncryptedSeed object or, in older key files, as a plain string
+    let bundle = seed
+        .get("bundle")
+        .unwrap_or(seed)
+        .as_str()
+        .map(|bundle| bundle.to_string())
+        .ok_or(napi::Error::from_reason(format!(
+            "{} value in the key file is not of type string",
+            field_path
+        )))?;
+
+    let derivation_path = seed
+        .get("derivationPath")
+        .and_then(|path| serde_json::from_value(path.clone()).ok());
+    let string_field = |name: &str| {
+        seed.get(name)
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string())
+    };
+
+    Ok(KeyFileSeed {
+        bundle,
+        derivation_path,
+        pub_key: string_field("pubKey"),
+        checksum: string_field("checksum"),
+    })
+}
+
+/// Unlocks the given parent seed bundle, derives the sub seed at the given index
+/// and encrypts it with the same passphrase
+pub(crate) async fn derive_encrypted_seed(
+    parent_seed: KeyFileSeed,
+    default_parent_derivation_path: Vec<u32>,
+    passphrase: Passphrase,
+    index: u32,
+    pw_hash_limits: Option<PwHashLimitsPreset>,
+    parent_seed_name: &str,
+    seed_name: &str,
+) -> napi::Result<EncryptedSeed> {
+    let unlocked_parent_seed =
+        unlock_key_file_seed(&parent_seed, &passphrase, parent_seed_name).await?;
+
+    let seed = unlocked_parent_seed.derive(index).await.map_err(|e| {
+        napi::Error::from_reason(format!("Failed to derive {} {}: {}", seed_name, index, e))
+    })?;
+
+    let mut derivation_path = parent_seed
+        .derivation_path
+        .unwrap_or(default_parent_derivation_path);
+    derivation_path.push(index);
+
+    lock_seed(
+        &seed,
+        &passphrase,
+        None,
+        derivation_path,
+        pw_hash_limits.map_or(PwHashLimits::Moderate, PwHashLimits::from),
+        &format!("{} {}", seed_name, index),
+    )
+    .await
+}
+
+/// Unlocks a seed of a KeyFile and checks that it matches the public key and checksum
+/// stored along with it, so that a swapped or corrupted entry is not used silently
+pub(crate) async fn unlock_key_file_seed(
+    seed: &KeyFileSeed,
+    passphrase: &Passphrase,
+    seed_name: &str,
+) -> napi::Result<UnlockedSeedBundle> {
+    let unlocked_seed = unlock_device_bundle(
+        &seed.bundle,
+        SeedBundleSecret::Passphrase(passphrase.clone()),
+    )
+    .await
+    .map_err(|e| napi::Error::from_reason(format!("Failed to unlock {}: {}", seed_name, e)))?;
+
+    let pub_key = agent_pub_key_b64(&unlocked_seed);
+    match &seed.pub_key {
+        Some(expected_pub_key) if *expected_pub_key != pub_key => {
+            return Err(napi::Error::from_reason(format!(
+                "The public key of the {} does not match the key file. Expected {} but got {}",
+                seed_name, expected_pub_key, pub_key
+            )))
+        }
+        _ => (),
+    }
+    if let Some(expected_checksum) = &seed.checksum {
+        let checksum = short_checksum(&unlocked_seed.get_sign_pub_key().read_lock())?;
+        if *expected_checksum != checksum {
+            return Err(napi::Error::from_reason(format!(
+                "The checksum of the {} does not match the key file. Expected {} but got {}",
+                seed_name, expected_checksum, checksum
+            )));
+        }
+    }
+    Ok(unlocked_seed)
+}
+
+/// Unlocks the device seeds seed of a KeyFile and derives the device seed at the
+/// given index from it, e.g. to onboard an additional device from the same root seed
+///
+/// The derived device seed is encrypted with the same passphrase and, unless
+/// specified otherwise, the "moderate" argon2 limits.
+#[napi]
+pub async fn derive_device_seed(
+    key_file_json_or_device_seeds_seed_b64: String,
+    passphrase: Passphrase,
+    index: u32,
+    pw_hash_limits: Option<PwHashLimitsPreset>,
+) -> napi::Result<EncryptedSeed> {
+    let device_seeds_seed = seed_bundle_from_key_file(
+        &key_file_json_or_device_seeds_seed_b64,
+        &[&["deviceSeedsSeed"]],
+    )?;
+
+    derive_encrypted_seed(
+        device_seeds_seed,
+        vec![DEFAULT_DEVICE_SEEDS_SEED_INDEX],
+        passphrase,
+        index,
+        pw_hash_limits,
+        "device seeds seed",
+        "device seed",
+    )
+    .await
+}
+
+/// Reads a seed of a key file without format version, which stored seeds either as
+/// plain base64 strings or as objects without the newer fields
+fn legacy_seed(
+    key_file: &serde_json::Value,
+    field: &str,
+    derivation_path: Vec<u32>,
+) -> napi::Result<EncryptedSeed> {
+    let seed = key_file.get(field).ok_or(napi::Error::from_reason(format!(
+        "{} field not found in the key file",
+        field
+    )))?;
+    parse_legacy_seed(seed, field, derivation_path)
+}
+
+fn parse_legacy_seed(
+    seed: &serde_json::Value,
+    field: &str,
+    derivation_path: Vec<u32>,
+) -> napi::Result<EncryptedSeed> {
+    let string_field = |name: &str| {
+        seed.get(name)
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string())
+    };
+
+    let bundle = seed
+        .as_str()
+        .map(|bundle| bundle.to_string())
+        .or_else(|| string_field("bundle"))
+        .ok_or(napi::Error::from_reason(format!(
+            "{} value in the key file is neither a string nor an encrypted seed",
+            field
+        )))?;
+    let ciphers = seed
+        .get("ciphers")
+        .and_then(|ciphers| serde_json::from_value(ciphers.clone()).ok())
+        .unwrap_or(vec!["PwHash".to_string()]);
+    let derivation_path = seed
+        .get("derivationPath")
+        .and_then(|path| serde_json::from_value(path.clone()).ok())
+        .unwrap_or(derivation_path);
+
+    Ok(EncryptedSeed {
+        bundle,
+        ciphers,
+        pub_key: string_field("pubKey"),
+        derivation_path,
+        checksum: string_field("checksum"),
+        pw_hash_limits: None,
+    })
+}
+
+fn legacy_seed_map(
+    key_file: &serde_json::Value,
+    map_field: &str,
+    single_seed_field: &str,
+    parent_index: u32,
+) -> napi::Result<BTreeMap<String, EncryptedSeed>> {
+    match key_file.get(map_field).and_then(|map| map.as_object()) {
+        Some(map) => map
+            .iter()
+            .map(|(index_string, seed)| {
+                let index = index_string.parse::<u32>().map_err(|_| {
+                    napi::Error::from_reason(format!(
+                        "Invalid index {} in {} of the key file",
+                        index_string, map_field
+                    ))
+                })?;
+                let seed = parse_legacy_seed(seed, index_string, vec![parent_index, index])?;
+                Ok((index_string.clone(), seed))
+            })
+            .collect(),
+        None => {
+            let seed = legacy_seed(key_file, single_seed_field, vec![parent_index, 0])?;
+            Ok(BTreeMap::from([("0".to_string(), seed)]))
+        }
+    }
+}
+
+/// Migrates a key file without format version, which always followed the default
+/// derivation conventions
+fn migrate_legacy_key_file(key_file: &serde_json::Value) -> napi::Result<KeyFile> {
+    Ok(KeyFile {
+        format_version: KEY_FILE_FORMAT_VERSION,
+        kdf: KeyFileKdf::default(),
+        root_seed: legacy_seed(key_file, "rootSeed", vec![])?,
+        revocation_seed: legacy_seed(
+            key_file,
+            "revocationSeed",
+            vec![DEFAULT_REVOCATION_SEED_INDEX],
+        )?,
+        device_seeds_seed: legacy_seed(
+            key_file,
+            "deviceSeedsSeed",
+            vec![DEFAULT_DEVICE_SEEDS_SEED_INDEX],
+        )?,
+        revocation_keys: legacy_seed_map(
+            key_file,
+            "revocationKeys",
+            "revocationKey0",
+            DEFAULT_REVOCATION_SEED_INDEX,
+        )?,
+        device_seeds: legacy_seed_map(
+            key_file,
+            "deviceSeeds",
+            "deviceSeed0",
+            DEFAULT_DEVICE_SEEDS_SEED_INDEX,
+        )?,
+        timestamp: key_file
+            .get("timestamp")
+            .and_then(|timestamp| timestamp.as_f64())
+            .unwrap_or_default(),
+    })
+}
+
+async fn validate_seed(
+    seed: &EncryptedSeed,
+    name: &str,
+    expected_parent_path: Option<&[u32]>,
+) -> napi::Result<()> {
+    let invalid = |reason: String| {
+        napi::Error::from_reason(format!("Invalid {} in key file: {}", name, reason))
+    };
+
+    let locked_bundle = base64::decode_config(&seed.bundle, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| invalid(format!("Failed to decode seed bundle: {}", e)))?;
+    UnlockedSeedBundle::from_locked(&locked_bundle)
+        .await
+        .map_err(|e| invalid(format!("Failed to parse seed bundle: {}", e)))?;
+
+    match expected_parent_path {
+        None if !seed.derivation_path.is_empty() => {
+            return Err(invalid(
+                "the root seed must have an empty derivation path".into(),
+            ))
+        }
+        Some(parent_path)
+            if seed.derivation_path.len() != parent_path.len() + 1
+                || !seed.derivation_path.starts_with(parent_path) =>
+        {
+            return Err(invalid(format!(
+                "derivation path {:?} is not below {:?}",
+                seed.derivation_path, parent_path
+            )))
+        }
+        _ => (),
+    }
+
+    if let Some(pub_key) = &seed.pub_key {
+        let pub_key = AgentPubKey::from(
+            AgentPubKeyB64::from_b64_str(pub_key)
+                .map_err(|e| invalid(format!("Failed to parse public key: {}", e)))?,
+        );
+        if let Some(checksum) = &seed.checksum {
+            if *checksum != short_checksum(pub_key.get_raw_32())? {
+                return Err(invalid(
+                    "checksum does not match the public key".to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn validate_key_file(key_file: &KeyFile) -> napi::Result<()> {
+    let kdf = KeyFileKdf::default();
+    if key_file.kdf.seed_bundle_format != kdf.seed_bundle_format {
+        return Err(napi::Error::from_reason(format!(
+            "Unsupported seed bundle format {}",
+            key_file.kdf.seed_bundle_format
+        )));
+    }
+
+    validate_seed(&key_file.root_seed, "root seed", None).await?;
+    validate_seed(&key_file.revocation_seed, "revocation seed", Some(&[])).await?;
+    validate_seed(&key_file.device_seeds_seed, "device seeds seed", Some(&[])).await?;
+
+    let seed_maps = [
+        (
+            &key_file.revocation_keys,
+            "revocation key",
+            &key_file.revocation_seed,
+        ),
+        (
+            &key_file.device_seeds,
+            "device seed",
+            &key_file.device_seeds_seed,
+        ),
+    ];
+    for (seeds, name, parent) in seed_maps {
+        for (index, seed) in seeds {
+            let name = format!("{} {}", name, index);
+            validate_seed(seed, &name, Some(&parent.derivation_path)).await?;
+            if seed.derivation_path.last().map(|i| i.to_string()).as_ref() != Some(index) {
+                return Err(napi::Error::from_reason(format!(
+                    "Invalid {} in key file: key does not match the derivation path {:?}",
+                    name, seed.derivation_path
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses and validates the JSON of a key file, migrating key files of older
+/// format versions to the current one
+#[napi]
+pub async fn parse_key_file(key_file_json: String) -> napi::Result<KeyFile> {
+    let key_file_value: serde_json::Value = serde_json::from_str(&key_file_json)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to parse key file JSON: {}", e)))?;
+
+    let format_version = match key_file_value.get("formatVersion") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or(napi::Error::from_reason(
+                "formatVersion of the key file is not a valid version number",
+            ))?,
+    };
+
+    let mut key_file = match format_version {
+        0 => migrate_legacy_key_file(&key_file_value)?,
+        KEY_FILE_FORMAT_VERSION => serde_json::from_value(key_file_value)
+            .map_err(|e| napi::Error::from_reason(format!("Failed to parse key file: {}", e)))?,
+        _ => {
+            return Err(napi::Error::from_reason(format!(
+                "Key file format version {} is not supported. The latest supported version is {}",
+                format_version, KEY_FILE_FORMAT_VERSION
+            )))
+        }
+    };
+
+    validate_key_file(&key_file).await?;
+
+    // Seeds of older key files don't report their argon2 limits yet
+    let seeds = [
+        &mut key_file.root_seed,
+        &mut key_file.revocation_seed,
+        &mut key_file.device_seeds_seed,
+    ]
+    .into_iter()
+    .chain(key_file.revocation_keys.values_mut())
+    .chain(key_file.device_seeds.values_mut());
+    for seed in seeds.filter(|seed| seed.pw_hash_limits.is_none()) {
+        seed.pw_hash_limits = Some(get_pw_hash_limits(seed.bundle.clone())?);
+    }
+
+    Ok(key_file)
+}
+
+/// Validates the key file and serializes it to JSON for storing it on disk
+#[napi]
+pub async fn serialize_key_file(key_file: KeyFile) -> napi::Result<String> {
+    if key_file.format_version != KEY_FILE_FORMAT_VERSION {
+        return Err(napi::Error::from_reason(format!(
+            "Cannot serialize key file of format version {}, only version {} is supported",
+            key_file.format_version, KEY_FILE_FORMAT_VERSION
+        )));
+    }
+
+    validate_key_file(&key_file).await?;
+
+    serde_json::to_string_pretty(&key_file)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to serialize key file: {}", e)))
+}
+
+/// Builds an UnlockedSeedBundle from a raw 32 byte seed, e.g. one restored from a backup
+///
+/// hc_seed_bundle only constructs unlocked seed bundles from random seeds or by unlocking
+/// them, so the seed is locked in memory with a throwaway passphrase and minimal argon2
+/// limits first. The locked bundle never leaves this function. Its encoding mirrors the
+/// one of the hc_seed_bundle version pinned in Cargo.toml, see encode_pw_hash_seed_bundle.
+pub(crate) async fn unlocked_seed_bundle_from_seed(
+    seed: &[u8],
+) -> napi::Result<UnlockedSeedBundle> {
+    use sodoken::secretstream::xchacha20poly1305::SecretStreamEncrypt;
+
+    let to_napi_error =
+        |e: one_err::OneErr| napi::Error::from_reason(format!("Failed to restore seed: {}", e));
+    if seed.len() != 32 {
+        return Err(napi::Error::from_reason(format!(
+            "Expected a seed of 32 bytes but got {} bytes",
+            seed.len()
+        )));
+    }
+
+    let seed_buf = sodoken::BufWriteSized::<32>::new_mem_locked().map_err(to_napi_error)?;
+    seed_buf.write_lock().copy_from_slice(seed);
+
+    let passphrase = Passphrase::new("restore".to_string());
+    let pw_hash = sodoken::BufWrite::new_mem_locked(64).map_err(to_napi_error)?;
+    sodoken::hash::blake2b::hash(pw_hash.clone(), passphrase.to_buf_read()?)
+        .await
+        .map_err(to_napi_error)?;
+
+    let limits = PwHashLimits::Minimum;
+    let salt = sodoken::BufWriteSized::<16>::new_no_lock();
+    sodoken::random::bytes_buf(salt.clone())
+        .await
+        .map_err(to_napi_error)?;
+    let secret = sodoken::BufWriteSized::<32>::new_mem_locked().map_err(to_napi_error)?;
+    sodoken::hash::argon2id::hash(
+        secret.clone(),
+        pw_hash,
+        salt.clone(),
+        limits.as_ops_limit(),
+        limits.as_mem_limit(),
+    )
+    .await
+    .map_err(to_napi_error)?;
+
+    let header = sodoken::BufWriteSized::<24>::new_no_lock();
+    let cipher = sodoken::BufWriteSized::<49>::new_no_lock();
+    SecretStreamEncrypt::new(secret, header.clone())
+        .map_err(to_napi_error)?
+        .push_final(
+            seed_buf.to_read_sized(),
+            <Option<sodoken::BufRead>>::None,
+            cipher.clone(),
+        )
+        .await
+        .map_err(to_napi_error)?;
+
+    let locked_bundle = encode_pw_hash_seed_bundle(
+        &salt.read_lock(),
+        limits.as_mem_limit(),
+        limits.as_ops_limit(),
+        &header.read_lock(),
+        &cipher.read_lock(),
+    )?;
+
+    unlock_seed_bundle(&locked_bundle, SeedBundleSecret::Passphrase(passphrase)).await
+}
+
+/// Encodes a seed bundle with a single PwHash cipher in the msgpack layout written by
+/// hc_seed_bundle: ["hcsb0", [["pw", salt, mem_limit, ops_limit, header, cipher]], app_data]
+///
+/// The layout is checked byte for byte against bundles locked by hc_seed_bundle in the tests.
+fn encode_pw_hash_seed_bundle(
+    salt: &[u8],
+    mem_limit: u32,
+    ops_limit: u32,
+    header: &[u8],
+    cipher: &[u8],
+) -> napi::Result<Vec<u8>> {
+    let binary = |bytes: &[u8]| rmpv::Value::Binary(bytes.to_vec());
+    let pw_hash_cipher = rmpv::Value::Array(vec![
+        "pw".into(),
+        binary(salt),
+        mem_limit.into(),
+        ops_limit.into(),
+        binary(header),
+        binary(cipher),
+    ]);
+    let bundle = rmpv::Value::Array(vec![
+        "hcsb0".into(),
+        rmpv::Value::Array(vec![pw_hash_cipher]),
+        binary(&[]),
+    ]);
+    let mut locked_bundle = Vec::new();
+    rmpv::encode::write_value(&mut locked_bundle, &bundle)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to encode seed bundle: {}", e)))?;
+    Ok(locked_bundle)
+}
+
+/// Encodes the root seed as a 24 word BIP-39 mnemonic, including its checksum
+pub(crate) fn mnemonic_from_root_seed(root_seed: &UnlockedSeedBundle) -> napi::Result<String> {
+    let mnemonic = bip39::Mnemonic::from_entropy(&root_seed.get_seed().read_lock())
+        .map_err(|e| napi::Error::from_reason(format!("Failed to encode mnemonic: {}", e)))?;
+    Ok(mnemonic.to_string())
+}
+
+/// Decodes a BIP-39 mnemonic written down by the user back into the root seed
+pub(crate) async fn root_seed_from_mnemonic(mnemonic: &str) -> napi::Result<UnlockedSeedBundle> {
+    let normalized_mnemonic = mnemonic
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+    let mnemonic =
+        bip39::Mnemonic::parse_in_normalized(bip39::Language::English, &normalized_mnemonic)
+            .map_err(|e| napi::Error::from_reason(format!("Invalid mnemonic: {}", e)))?;
+
+    unlocked_seed_bundle_from_seed(&mnemonic.to_entropy()).await
+}
+
+/// Unlocks the root seed of a KeyFile and returns it as a 24 word mnemonic that
+/// can be written down as a paper backup
+#[napi]
+pub async fn export_root_seed_mnemonic(
+    key_file_json_or_root_seed_b64: String,
+    passphrase: Passphrase,
+) -> napi::Result<String> {
+    let root_seed = seed_bundle_from_key_file(&key_file_json_or_root_seed_b64, &[&["rootSeed"]])?;
+    let root_seed = unlock_key_file_seed(&root_seed, &passphrase, "root seed").await?;
+
+    mnemonic_from_root_seed(&root_seed)
+}
+
+/// Restores the root seed from a mnemonic and re-derives the same seeds as
+/// generate_initial_seeds did for the same derivation spec
+///
+/// The seeds are encrypted with the given passphrase unless a separate passphrase
+/// is specified for them in the options.
+#[napi]
+pub async fn restore_key_file_from_mnemonic(
+    mnemonic: String,
+    passphrase: Passphrase,
+    options: Option<KeyGenerationOptions>,
+    progress: Option<SeedEncryptionProgressCallback>,
+) -> napi::Result<KeyFile> {
+    let root_seed = root_seed_from_mnemonic(&mnemonic).await?;
+
+    key_file_from_root_seed(root_seed, passphrase, options, |seed_progress| {
+        report_progress_to(&progress, seed_progress)
+    })
+    .await
+}
+
+/// Version of the format of root seed shares written by this library
+pub const SEED_SHARE_FORMAT_VERSION: u32 = 1;
+
+/// One of several Shamir secret shares of the root seed, meant to be handed to a
+/// trusted party. Any `threshold` shares of the same split rebuild the root seed.
+#[napi(object)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedShare {
+    pub format_version: u32,
+    /// Index of the share, starting at 1
+    pub index: u32,
+    pub threshold: u32,
+    pub share_count: u32,
+    /// Base64 (url safe, no padding) encoded share
+    pub share: String,
+    /// Short checksum of the share to detect typos and corrupted shares
+    pub checksum: String,
+    /// Base64 encoded ed25519 public key of the root seed, to verify the rebuilt seed
+    pub root_seed_pub_key: String,
+}
+
+/// Splits the root seed into `share_count` Shamir secret shares, any `threshold`
+/// of which are needed to rebuild it
+pub(crate) fn split_root_seed(
+    root_seed: &UnlockedSeedBundle,
+    share_count: u32,
+    threshold: u32,
+) -> napi::Result<Vec<SeedShare>> {
+    if threshold < 2 || threshold > share_count || share_count > u8::MAX as u32 {
+        return Err(napi::Error::from_reason(format!(
+            "Cannot split the root seed into {} shares with a threshold of {}. The threshold needs to be at least 2 and at most the number of shares, which can be at most {}",
+            share_count,
+            threshold,
+            u8::MAX
+        )));
+    }
+
+    let root_seed_pub_key = agent_pub_key_b64(root_seed);
+    sharks::Sharks(threshold as u8)
+        .dealer(&root_seed.get_seed().read_lock())
+        .take(share_count as usize)
+        .map(|share| {
+            let share = Vec::from(&share);
+            Ok(SeedShare {
+                format_version: SEED_SHARE_FORMAT_VERSION,
+                // The first byte of a share is its x coordinate, starting at 1
+                index: share[0] as u32,
+                threshold,
+                share_count,
+                checksum: short_checksum(&share)?,
+                share: base64::encode_config(&share, base64::URL_SAFE_NO_PAD),
+                root_seed_pub_key: root_seed_pub_key.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Rebuilds the root seed from at least `threshold` shares of the same split
+pub(crate) async fn root_seed_from_shares(
+    shares: &[SeedShare],
+) -> napi::Result<UnlockedSeedBundle> {
+    let first_share = shares.first().ok_or(napi::Error::from_reason(
+        "No shares given to rebuild the root seed from",
+    ))?;
+
+    let mut decoded_shares = Vec::new();
+    let mut indices = HashSet::new();
+    for share in shares {
+        let invalid = |reason: &str| {
+            napi::Error::from_reason(format!("Invalid share {}: {}", share.index, reason))
+        };
+
+        if share.format_version != SEED_SHARE_FORMAT_VERSION {
+            return Err(invalid(&format!(
+                "format version {} is not supported. The latest supported version is {}",
+                share.format_version, SEED_SHARE_FORMAT_VERSION
+            )));
+        }
+        if share.root_seed_pub_key != first_share.root_seed_pub_key
+            || share.threshold != first_share.threshold
+        {
+            return Err(invalid(
+                "the shares belong to different root seeds or splits",
+            ));
+        }
+        if !indices.insert(share.index) {
+            return Err(invalid("the share has been given more than once"));
+        }
+
+        let share_bytes = base64::decode_config(share.share.trim(), base64::URL_SAFE_NO_PAD)
+            .map_err(|e| invalid(&format!("failed to decode share: {}", e)))?;
+        if short_checksum(&share_bytes)? != share.checksum {
+            return Err(invalid("checksum does not match the share"));
+        }
+        let decoded_share = sharks::Share::try_from(share_bytes.as_slice()).map_err(invalid)?;
+        if share_bytes[0] as u32 != share.index {
+            return Err(invalid("index does not match the share"));
+        }
+        decoded_shares.push(decoded_share);
+    }
+
+    if (decoded_shares.len() as u32) < first_share.threshold {
+        return Err(napi::Error::from_reason(format!(
+            "{} of the {} shares are needed to rebuild the root seed but only {} were given",
+            first_share.threshold,
+            first_share.share_count,
+            decoded_shares.len()
+        )));
+    }
+
+    let seed = sharks::Sharks(first_share.threshold as u8)
+        .recover(&decoded_shares)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to rebuild the root seed: {}", e)))?;
+    let root_seed = unlocked_seed_bundle_from_seed(&seed).await?;
+
+    if agent_pub_key_b64(&root_seed) != first_share.root_seed_pub_key {
+        return Err(napi::Error::from_reason(
+            "The rebuilt root seed does not match the public key of the shares",
+        ));
+    }
+
+    Ok(root_seed)
+}
+
+/// Unlocks the root seed of a KeyFile and splits it into `share_count` shares
+/// for social recovery, any `threshold` of which are needed to rebuild it
+#[napi]
+pub async fn split_root_seed_into_shares(
+    key_file_json_or_root_seed_b64: String,
+    passphrase: Passphrase,
+    share_count: u32,
+    threshold: u32,
+) -> napi::Result<Vec<SeedShare>> {
+    let root_seed = seed_bundle_from_key_file(&key_file_json_or_root_seed_b64, &[&["rootSeed"]])?;
+    let root_seed = unlock_key_file_seed(&root_seed, &passphrase, "root seed").await?;
+
+    split_root_seed(&root_seed, share_count, threshold)
+}
+
+/// Rebuilds the root seed from shares and re-derives the same seeds as
+/// generate_initial_seeds did for the same derivation spec
+///
+/// The seeds are encrypted with the given passphrase unless a separate passphrase
+/// is specified for them in the options.
+#[napi]
+pub async fn restore_key_file_from_shares(
+    shares: Vec<SeedShare>,
+    passphrase: Passphrase,
+    options: Option<KeyGenerationOptions>,
+    progress: Option<SeedEncryptionProgressCallback>,
+) -> napi::Result<KeyFile> {
+    let root_seed = root_seed_from_shares(&shares).await?;
+
+    key_file_from_root_seed(root_seed, passphrase, options, |seed_progress| {
+        report_progress_to(&progress, seed_progress)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use napi::bindgen_prelude::block_on;
+
+    fn fast_options() -> Option<KeyGenerationOptions> {
+        Some(KeyGenerationOptions {
+            derivation: Some(DerivationSpec {
+                revocation_seed_index: None,
+                device_seeds_seed_index: None,
+                revocation_key_count: Some(2),
+                revocation_key_indices: None,
+                device_seed_count: None,
+                device_seed_indices: Some(vec![0, 3]),
+            }),
+            passphrases: None,
+            security_questions: None,
+            pw_hash_limits: Some(PwHashLimitsPreset::Interactive),
+        })
+    }
+
+    fn pub_keys(key_file: &KeyFile) -> Vec<Option<String>> {
+        [
+            &key_file.root_seed,
+            &key_file.revocation_seed,
+            &key_file.device_seeds_seed,
+        ]
+        .into_iter()
+        .chain(key_file.revocation_keys.values())
+        .chain(key_file.device_seeds.values())
+        .map(|seed| seed.pub_key.clone())
+        .collect()
+    }
+
+    #[test]
+    fn pw_hash_limits_are_read_back_for_each_preset() {
+        block_on(async {
+            let seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let presets = [
+                (PwHashLimits::Minimum, "minimum", true),
+                (PwHashLimits::Interactive, "interactive", true),
+                (PwHashLimits::Moderate, "moderate", false),
+                (PwHashLimits::Sensitive, "sensitive", false),
+            ];
+
+            for (limits, preset, weak) in presets {
+                let encrypted_seed = lock_seed(
+                    &seed,
+                    &"passphrase".to_string().into(),
+                    None,
+                    vec![],
+                    limits,
+                    "seed",
+                )
+                .await
+                .unwrap();
+
+                let info = get_pw_hash_limits(encrypted_seed.bundle).unwrap();
+                assert_eq!(info.preset, preset);
+                assert_eq!(info.mem_limit, limits.as_mem_limit());
+                assert_eq!(info.ops_limit, limits.as_ops_limit());
+                assert_eq!(info.weak, weak);
+            }
+        })
+    }
+
+    #[test]
+    fn progress_is_reported_after_each_completed_encryption() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let progress = std::sync::Mutex::new(Vec::new());
+            key_file_from_root_seed(
+                root_seed,
+                "passphrase".to_string().into(),
+                fast_options(),
+                |seed_progress| progress.lock().unwrap().push(seed_progress),
+            )
+            .await
+            .unwrap();
+
+            let progress = progress.into_inner().unwrap();
+            assert_eq!(progress.len(), 14);
+            assert!(progress.iter().all(|p| p.total_count == 7));
+            let finished_counts: Vec<u32> = progress
+                .iter()
+                .filter(|p| p.finished)
+                .map(|p| p.encrypted_count)
+                .collect();
+            assert_eq!(finished_counts, (1..=7).collect::<Vec<u32>>());
+            for finished in progress.iter().filter(|p| p.finished) {
+                let started = progress
+                    .iter()
+                    .position(|p| !p.finished && p.seed_name == finished.seed_name);
+                let finished_position = progress
+                    .iter()
+                    .position(|p| p.finished && p.seed_name == finished.seed_name);
+                assert!(started < finished_position);
+            }
+        })
+    }
+
+    #[test]
+    fn seed_bundle_from_seed_holds_the_same_seed() {
+        block_on(async {
+            let seed: Vec<u8> = (0..32).collect();
+            let seed_bundle = unlocked_seed_bundle_from_seed(&seed).await.unwrap();
+
+            assert_eq!(seed_bundle.get_seed().read_lock().to_vec(), seed);
+            let secret_key = ed25519_dalek::SecretKey::from_bytes(&seed).unwrap();
+            let pub_key = ed25519_dalek::PublicKey::from(&secret_key);
+            assert_eq!(
+                seed_bundle.get_sign_pub_key().read_lock().to_vec(),
+                pub_key.as_bytes().to_vec()
+            );
+
+            let error = unlocked_seed_bundle_from_seed(&seed[..31])
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(error.reason, "Expected a seed of 32 bytes but got 31 bytes");
+        })
+    }
+
+    #[test]
+    fn pw_hash_seed_bundle_encoding_matches_hc_seed_bundle() {
+        block_on(async {
+            let seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let passphrase = Passphrase::new("passphrase".to_string())
+                .to_buf_read()
+                .unwrap();
+            let locked_bundle = PwHashLimits::Minimum
+                .with_exec(|| seed.lock().add_pwhash_cipher(passphrase))
+                .lock()
+                .await
+                .unwrap();
+
+            let value = rmpv::decode::read_value(&mut &locked_bundle[..]).unwrap();
+            let ciphers = value[1].as_array().unwrap();
+            assert_eq!(ciphers.len(), 1);
+            let cipher = ciphers[0].as_array().unwrap();
+            assert_eq!(cipher[0].as_str(), Some("pw"));
+
+            let encoded = encode_pw_hash_seed_bundle(
+                cipher[1].as_slice().unwrap(),
+                cipher[2].as_u64().unwrap() as u32,
+                cipher[3].as_u64().unwrap() as u32,
+                cipher[4].as_slice().unwrap(),
+                cipher[5].as_slice().unwrap(),
+            )
+            .unwrap();
+            assert_eq!(encoded, locked_bundle.to_vec());
+        })
+    }
+
+    #[test]
+    fn mnemonic_round_trip_restores_root_seed() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let mnemonic = mnemonic_from_root_seed(&root_seed).unwrap();
+            assert_eq!(mnemonic.split(' ').count(), 24);
+
+            let restored_root_seed = root_seed_from_mnemonic(&mnemonic).await.unwrap();
+            assert_eq!(
+                agent_pub_key_b64(&restored_root_seed),
+                agent_pub_key_b64(&root_seed)
+            );
+            assert_eq!(
+                mnemonic_from_root_seed(&restored_root_seed).unwrap(),
+                mnemonic
+            );
+
+            // Restoring tolerates the formatting of a handwritten mnemonic
+            let handwritten_mnemonic =
+                format!("  {}\n", mnemonic.to_uppercase().replace(' ', "\n "));
+            let restored_root_seed = root_seed_from_mnemonic(&handwritten_mnemonic)
+                .await
+                .unwrap();
+            assert_eq!(
+                agent_pub_key_b64(&restored_root_seed),
+                agent_pub_key_b64(&root_seed)
+            );
+        })
+    }
+
+    #[test]
+    fn mnemonic_with_wrong_checksum_is_rejected() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let mnemonic = mnemonic_from_root_seed(&root_seed).unwrap();
+            // The lowest bits of the last word are checksum bits
+            let mut words: Vec<&str> = mnemonic.split(' ').collect();
+            let word_list = bip39::Language::English.word_list();
+            let last_word_index = word_list.iter().position(|w| w == words.last().unwrap());
+            words[23] = word_list[last_word_index.unwrap() ^ 1];
+
+            let error = root_seed_from_mnemonic(&words.join(" "))
+                .await
+                .err()
+                .unwrap();
+            assert!(error.reason.starts_with("Invalid mnemonic"));
+        })
+    }
+
+    #[test]
+    fn key_file_restored_from_mnemonic_derives_the_same_seeds() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let mnemonic = mnemonic_from_root_seed(&root_seed).unwrap();
+            let key_file = key_file_from_root_seed(
+                root_seed,
+                "passphrase".to_string().into(),
+                fast_options(),
+                |_| (),
+            )
+            .await
+            .unwrap();
+
+            for _ in 0..2 {
+                let restored_root_seed = root_seed_from_mnemonic(&mnemonic).await.unwrap();
+                let restored_key_file = key_file_from_root_seed(
+                    restored_root_seed,
+                    "new passphrase".to_string().into(),
+                    fast_options(),
+                    |_| (),
+                )
+                .await
+                .unwrap();
+
+                assert_eq!(pub_keys(&restored_key_file), pub_keys(&key_file));
+                assert_eq!(
+                    restored_key_file.device_seeds["3"].derivation_path,
+                    vec![DEFAULT_DEVICE_SEEDS_SEED_INDEX, 3]
+                );
+            }
+        })
+    }
+
+    #[test]
+    fn serialized_key_file_parses_back_unchanged() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let key_file = key_file_from_root_seed(
+                root_seed,
+                "passphrase".to_string().into(),
+                fast_options(),
+                |_| (),
+            )
+            .await
+            .unwrap();
+
+            let key_file_json = serialize_key_file(key_file.clone()).await.unwrap();
+            let parsed_key_file = parse_key_file(key_file_json).await.unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&parsed_key_file).unwrap(),
+                serde_json::to_value(&key_file).unwrap()
+            );
+        })
+    }
+
+    #[test]
+    fn device_seed_is_not_derived_from_a_swapped_seed() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let mut key_file = key_file_from_root_seed(
+                root_seed,
+                "passphrase".to_string().into(),
+                fast_options(),
+                |_| (),
+            )
+            .await
+            .unwrap();
+            key_file.device_seeds_seed.bundle = key_file.revocation_seed.bundle.clone();
+
+            let error = derive_device_seed(
+                serialize_key_file(key_file).await.unwrap(),
+                "passphrase".to_string().into(),
+                1,
+                Some(PwHashLimitsPreset::Interactive),
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(error
+                .reason
+                .starts_with("The public key of the device seeds seed does not match"));
+        })
+    }
+
+    #[test]
+    fn legacy_key_file_is_migrated() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let key_file = key_file_from_root_seed(
+                root_seed,
+                "passphrase".to_string().into(),
+                fast_options(),
+                |_| (),
+            )
+            .await
+            .unwrap();
+
+            // Key files without format version stored the seeds as plain base64 strings
+            // and only contained the seeds at index 0
+            let legacy_key_file_json = serde_json::json!({
+                "rootSeed": key_file.root_seed.bundle,
+                "revocationSeed": key_file.revocation_seed.bundle,
+                "deviceSeedsSeed": key_file.device_seeds_seed.bundle,
+                "revocationKey0": key_file.revocation_keys["0"].bundle,
+                "deviceSeed0": key_file.device_seeds["0"].bundle,
+                "timestamp": 1700000000000.0,
+            })
+            .to_string();
+
+            let migrated_key_file = parse_key_file(legacy_key_file_json).await.unwrap();
+
+            assert_eq!(migrated_key_file.format_version, KEY_FILE_FORMAT_VERSION);
+            assert_eq!(migrated_key_file.timestamp, 1700000000000.0);
+            assert_eq!(
+                migrated_key_file.root_seed.bundle,
+                key_file.root_seed.bundle
+            );
+            assert!(migrated_key_file.root_seed.derivation_path.is_empty());
+            assert_eq!(
+                migrated_key_file.revocation_seed.derivation_path,
+                vec![DEFAULT_REVOCATION_SEED_INDEX]
+            );
+            assert_eq!(
+                migrated_key_file.device_seeds_seed.derivation_path,
+                vec![DEFAULT_DEVICE_SEEDS_SEED_INDEX]
+            );
+            assert_eq!(
+                migrated_key_file.revocation_keys.keys().collect::<Vec<_>>(),
+                vec!["0"]
+            );
+            assert_eq!(
+                migrated_key_file.revocation_keys["0"].derivation_path,
+                vec![DEFAULT_REVOCATION_SEED_INDEX, 0]
+            );
+            assert_eq!(
+                migrated_key_file.device_seeds["0"].bundle,
+                key_file.device_seeds["0"].bundle
+            );
+            assert_eq!(
+                migrated_key_file.device_seeds["0"].derivation_path,
+                vec![DEFAULT_DEVICE_SEEDS_SEED_INDEX, 0]
+            );
+            assert_eq!(migrated_key_file.device_seeds["0"].ciphers, vec!["PwHash"]);
+            assert!(migrated_key_file.device_seeds["0"].pub_key.is_none());
+            // The argon2 limits are read from the seed bundles themselves
+            assert_eq!(
+                migrated_key_file.device_seeds["0"]
+                    .pw_hash_limits
+                    .as_ref()
+                    .unwrap()
+                    .preset,
+                "interactive"
+            );
+        })
+    }
+
+    #[test]
+    fn any_threshold_shares_rebuild_the_root_seed() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let shares = split_root_seed(&root_seed, 5, 3).unwrap();
+            assert_eq!(shares.len(), 5);
+
+            for share_indices in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+                let some_shares: Vec<SeedShare> =
+                    share_indices.iter().map(|i| shares[*i].clone()).collect();
+                let rebuilt_root_seed = root_seed_from_shares(&some_shares).await.unwrap();
+                assert_eq!(
+                    agent_pub_key_b64(&rebuilt_root_seed),
+                    agent_pub_key_b64(&root_seed)
+                );
+            }
+        })
+    }
+
+    #[test]
+    fn too_few_or_corrupted_shares_are_rejected() {
+        block_on(async {
+            let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let shares = split_root_seed(&root_seed, 3, 2).unwrap();
+
+            let error = root_seed_from_shares(&shares[..1]).await.err().unwrap();
+            assert!(error.reason.contains("only 1 were given"));
+
+            let mut corrupted_share = shares[1].clone();
+            corrupted_share.share = shares[2].share.clone();
+            let error = root_seed_from_shares(&[shares[0].clone(), corrupted_share])
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.reason,
+                "Invalid share 2: checksum does not match the share"
+            );
+        })
+    }
+}
@@ -0,0 +1,367 @@
+use hc_seed_bundle::UnlockedSeedBundle;
+use holo_hash::{ActionHash, ActionHashB64, AgentPubKey, AgentPubKeyB64};
+
+use crate::key_generation::{
+    agent_pub_key_b64, derive_encrypted_seed, seed_bundle_from_key_file, unlock_key_file_seed,
+    EncryptedSeed, PwHashLimitsPreset, DEFAULT_REVOCATION_SEED_INDEX,
+};
+use crate::passphrase::Passphrase;
+
+/// Signature of a revocation key authorizing a revocation, as expected by DeepKey
+#[napi(object)]
+pub struct RevocationAuthorization {
+    /// Index of the revocation key in the authorized signers of the DeepKey change rule,
+    /// at most 255
+    pub authorizer_index: u32,
+    /// Base64 encoded ed25519 public key of the revocation key
+    pub authorizer_pub_key: String,
+    pub signature: Vec<u8>,
+}
+
+/// Payload for revoking a key registration in DeepKey
+#[napi(object)]
+pub struct KeyRevocationPayload {
+    /// Base64 encoded action hash of the key registration to revoke
+    pub prior_key_registration: String,
+    pub revocation_authorization: Vec<RevocationAuthorization>,
+}
+
+/// Payload for replacing a key registration with a new key in DeepKey
+///
+/// The new key still needs to sign the key generation itself, which has to happen
+/// wherever its private key is held, i.e. in lair.
+#[napi(object)]
+pub struct KeyUpdatePayload {
+    pub revocation: KeyRevocationPayload,
+    /// Base64 encoded public key of the key replacing the revoked one
+    pub new_key: String,
+}
+
+/// Unlocks the revocation key at the given index of a KeyFile or a base64 encoded
+/// locked revocation key bundle
+async fn unlock_revocation_key(
+    key_file_json_or_revocation_key_b64: &str,
+    passphrase: Passphrase,
+    index: u32,
+) -> napi::Result<UnlockedSeedBundle> {
+    let index_string = index.to_string();
+    let indexed_field_path = ["revocationKeys", index_string.as_str()];
+    let mut field_paths: Vec<&[&str]> = vec![&indexed_field_path];
+    // Key files generated before revocation keys were keyed by index
+    if index == 0 {
+        field_paths.push(&["revocationKey0"]);
+    }
+
+    let revocation_key =
+        seed_bundle_from_key_file(key_file_json_or_revocation_key_b64, &field_paths)?;
+
+    unlock_key_file_seed(
+        &revocation_key,
+        &passphrase,
+        &format!("revocation key {}", index),
+    )
+    .await
+}
+
+async fn sign_revocation(
+    revocation_key: &UnlockedSeedBundle,
+    prior_key_registration: String,
+    authorizer_index: u32,
+) -> napi::Result<KeyRevocationPayload> {
+    // DeepKey stores the index of an authorized signer as a u8
+    if u8::try_from(authorizer_index).is_err() {
+        return Err(napi::Error::from_reason(format!(
+            "Authorizer index {} is out of range, DeepKey only supports indices up to {}",
+            authorizer_index,
+            u8::MAX
+        )));
+    }
+
+    let prior_key_registration_hash: ActionHash =
+        ActionHashB64::from_b64_str(&prior_key_registration)
+            .map_err(|e| {
+                napi::Error::from_reason(format!(
+                    "Failed to parse prior key registration action hash: {}",
+                    e
+                ))
+            })?
+            .into();
+
+    let signature = revocation_key
+        .sign_detached(prior_key_registration_hash.get_raw_39().to_vec())
+        .await
+        .map_err(|e| {
+            napi::Error::from_reason(format!("Failed to sign with revocation key: {}", e))
+        })?;
+
+    let signature = signature.read_lock().to_vec();
+
+    Ok(KeyRevocationPayload {
+        prior_key_registration,
+        revocation_authorization: vec![RevocationAuthorization {
+            authorizer_index,
+            authorizer_pub_key: agent_pub_key_b64(revocation_key),
+            signature,
+        }],
+    })
+}
+
+/// Returns the base64 encoded public key of the revocation key at the given index,
+/// which checks at the same time that the revocation key can be unlocked
+#[napi]
+pub async fn get_revocation_key_pub_key(
+    key_file_json_or_revocation_key_b64: String,
+    passphrase: Passphrase,
+    index: u32,
+) -> napi::Result<String> {
+    let revocation_key =
+        unlock_revocation_key(&key_file_json_or_revocation_key_b64, passphrase, index).await?;
+
+    Ok(agent_pub_key_b64(&revocation_key))
+}
+
+/// Signs the revocation of the given key registration with the revocation key
+/// at the given index
+#[napi]
+pub async fn create_key_revocation(
+    key_file_json_or_revocation_key_b64: String,
+    passphrase: Passphrase,
+    index: u32,
+    prior_key_registration: String,
+    authorizer_index: u32,
+) -> napi::Result<KeyRevocationPayload> {
+    let revocation_key =
+        unlock_revocation_key(&key_file_json_or_revocation_key_b64, passphrase, index).await?;
+
+    sign_revocation(&revocation_key, prior_key_registration, authorizer_index).await
+}
+
+/// Signs the revocation of the given key registration with the revocation key
+/// at the given index in order to replace it with the new key
+#[napi]
+pub async fn create_key_update(
+    key_file_json_or_revocation_key_b64: String,
+    passphrase: Passphrase,
+    index: u32,
+    prior_key_registration: String,
+    authorizer_index: u32,
+    new_key: String,
+) -> napi::Result<KeyUpdatePayload> {
+    let new_key: AgentPubKey = AgentPubKeyB64::from_b64_str(&new_key)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to parse new key: {}", e)))?
+        .into();
+
+    let revocation_key =
+        unlock_revocation_key(&key_file_json_or_revocation_key_b64, passphrase, index).await?;
+
+    let revocation =
+        sign_revocation(&revocation_key, prior_key_registration, authorizer_index).await?;
+
+    Ok(KeyUpdatePayload {
+        revocation,
+        new_key: AgentPubKeyB64::from(new_key).to_string(),
+    })
+}
+
+/// Derives the revocation key following the given one from the revocation seed,
+/// to be used once the current revocation key has been used or compromised
+///
+/// The new revocation key is encrypted with the same passphrase and, unless
+/// specified otherwise, the "moderate" argon2 limits.
+#[napi]
+pub async fn rotate_revocation_key(
+    key_file_json_or_revocation_seed_b64: String,
+    passphrase: Passphrase,
+    current_index: u32,
+    pw_hash_limits: Option<PwHashLimitsPreset>,
+) -> napi::Result<EncryptedSeed> {
+    let next_index = current_index
+        .checked_add(1)
+        .ok_or(napi::Error::from_reason(
+            "No revocation key index left to rotate to",
+        ))?;
+
+    let revocation_seed = seed_bundle_from_key_file(
+        &key_file_json_or_revocation_seed_b64,
+        &[&["revocationSeed"]],
+    )?;
+
+    derive_encrypted_seed(
+        revocation_seed,
+        vec![DEFAULT_REVOCATION_SEED_INDEX],
+        passphrase,
+        next_index,
+        pw_hash_limits,
+        "revocation seed",
+        "revocation key",
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::{key_file_from_root_seed, DerivationSpec, KeyGenerationOptions};
+    use ed25519_dalek::{PublicKey, Signature, Verifier};
+    use napi::bindgen_prelude::block_on;
+
+    const PRIOR_KEY_REGISTRATION: [u8; 32] = [7; 32];
+
+    async fn key_file_json() -> String {
+        let root_seed = UnlockedSeedBundle::new_random().await.unwrap();
+        let options = KeyGenerationOptions {
+            derivation: Some(DerivationSpec {
+                revocation_seed_index: None,
+                device_seeds_seed_index: None,
+                revocation_key_count: Some(2),
+                revocation_key_indices: None,
+                device_seed_count: None,
+                device_seed_indices: None,
+            }),
+            passphrases: None,
+            security_questions: None,
+            pw_hash_limits: Some(PwHashLimitsPreset::Interactive),
+        };
+        let key_file = key_file_from_root_seed(
+            root_seed,
+            Passphrase::new("passphrase".to_string()),
+            Some(options),
+            |_| (),
+        )
+        .await
+        .unwrap();
+        serde_json::to_string(&key_file).unwrap()
+    }
+
+    fn prior_key_registration() -> String {
+        ActionHashB64::from(ActionHash::from_raw_32(PRIOR_KEY_REGISTRATION.to_vec())).to_string()
+    }
+
+    fn assert_signed_by(authorization: &RevocationAuthorization, pub_key: &str) {
+        assert_eq!(authorization.authorizer_pub_key, pub_key);
+
+        let pub_key = AgentPubKey::from(AgentPubKeyB64::from_b64_str(pub_key).unwrap());
+        let pub_key = PublicKey::from_bytes(pub_key.get_raw_32()).unwrap();
+        let signature = Signature::try_from(authorization.signature.as_slice()).unwrap();
+        let prior_key_registration_hash = ActionHash::from_raw_32(PRIOR_KEY_REGISTRATION.to_vec());
+        pub_key
+            .verify(prior_key_registration_hash.get_raw_39(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn key_revocation_is_signed_by_the_revocation_key() {
+        block_on(async {
+            let key_file_json = key_file_json().await;
+            let passphrase = Passphrase::new("passphrase".to_string());
+
+            let pub_key = get_revocation_key_pub_key(key_file_json.clone(), passphrase.clone(), 1)
+                .await
+                .unwrap();
+            let revocation =
+                create_key_revocation(key_file_json, passphrase, 1, prior_key_registration(), 3)
+                    .await
+                    .unwrap();
+
+            assert_eq!(revocation.prior_key_registration, prior_key_registration());
+            assert_eq!(revocation.revocation_authorization.len(), 1);
+            assert_eq!(revocation.revocation_authorization[0].authorizer_index, 3);
+            assert_signed_by(&revocation.revocation_authorization[0], &pub_key);
+        })
+    }
+
+    #[test]
+    fn key_update_is_signed_by_the_revocation_key() {
+        block_on(async {
+            let key_file_json = key_file_json().await;
+            let passphrase = Passphrase::new("passphrase".to_string());
+            let new_key = AgentPubKeyB64::from(AgentPubKey::from_raw_32(vec![9; 32])).to_string();
+
+            let pub_key = get_revocation_key_pub_key(key_file_json.clone(), passphrase.clone(), 0)
+                .await
+                .unwrap();
+            let update = create_key_update(
+                key_file_json,
+                passphrase,
+                0,
+                prior_key_registration(),
+                0,
+                new_key.clone(),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(update.new_key, new_key);
+            assert_signed_by(&update.revocation.revocation_authorization[0], &pub_key);
+        })
+    }
+
+    #[test]
+    fn authorizer_index_beyond_deepkey_range_is_rejected() {
+        block_on(async {
+            let key_file_json = key_file_json().await;
+
+            let error = create_key_revocation(
+                key_file_json,
+                Passphrase::new("passphrase".to_string()),
+                0,
+                prior_key_registration(),
+                256,
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(error
+                .reason
+                .starts_with("Authorizer index 256 is out of range"));
+        })
+    }
+
+    #[test]
+    fn swapped_key_file_entries_are_rejected() {
+        block_on(async {
+            let mut key_file: serde_json::Value =
+                serde_json::from_str(&key_file_json().await).unwrap();
+            let passphrase = Passphrase::new("passphrase".to_string());
+            let swap_bundles = |key_file: &mut serde_json::Value, a: &str, b: &str| {
+                let bundle_a = key_file.pointer(a).unwrap().clone();
+                let bundle_b = key_file.pointer(b).unwrap().clone();
+                *key_file.pointer_mut(a).unwrap() = bundle_b;
+                *key_file.pointer_mut(b).unwrap() = bundle_a;
+            };
+            swap_bundles(
+                &mut key_file,
+                "/revocationKeys/0/bundle",
+                "/revocationKeys/1/bundle",
+            );
+            swap_bundles(
+                &mut key_file,
+                "/revocationSeed/bundle",
+                "/deviceSeedsSeed/bundle",
+            );
+            let key_file_json = key_file.to_string();
+
+            let error = create_key_revocation(
+                key_file_json.clone(),
+                passphrase.clone(),
+                0,
+                prior_key_registration(),
+                0,
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(error
+                .reason
+                .starts_with("The public key of the revocation key 0 does not match"));
+
+            let error = rotate_revocation_key(key_file_json, passphrase, 1, None)
+                .await
+                .err()
+                .unwrap();
+            assert!(error
+                .reason
+                .starts_with("The public key of the revocation seed does not match"));
+        })
+    }
+}
@@ -1,44 +1,59 @@
 #![deny(clippy::all)]
 
 use std::ops::Deref;
+use std::sync::Arc;
 
 use ed25519_dalek::{Keypair, PublicKey, SecretKey};
 use hc_seed_bundle::{LockedSeedCipher, UnlockedSeedBundle};
 use holo_hash::{AgentPubKey, AgentPubKeyB64};
 use holochain_zome_types::prelude::{Signature, ZomeCallUnsigned};
 use lair_keystore_api::{
-    dependencies::{sodoken::BufRead, url::Url},
+    dependencies::{one_err::OneErr, sodoken, url::Url},
+    encoding_types::X25519PubKey,
     ipc_keystore::ipc_keystore_connect,
-    lair_store::{LairEntryInfo, SeedInfo},
+    lair_store::LairEntryInfo,
     LairClient,
 };
-use pinentry::PassphraseInput;
-use secrecy::{ExposeSecret, SecretString};
 
 use napi::Result;
 
+use crate::host_config::{parse_host_config, HostConfig};
+use crate::key_generation::{agent_pub_key_b64, parse_key_file, unlock_key_file_seed};
+use crate::passphrase::{Passphrase, PassphraseCallback, PassphraseProvider, PassphraseSource};
 use crate::types::*;
 
+/// Tag of the seed imported seeds are encrypted for in transit if the keystore holds
+/// no seed an earlier version created for that
+const IMPORT_TRANSPORT_KEY_TAG: &str = "import-transport-key";
+/// Tag of the seed earlier versions encrypted imported seeds for, which is reused
+const LEGACY_IMPORT_RECIPIENT_TAG: &str = "import-decryption-key";
+/// Tags of the seeds kept in lair only to encrypt imported seeds
+const IMPORT_HELPER_TAGS: [&str; 3] = [
+    "import-encryption-key",
+    LEGACY_IMPORT_RECIPIENT_TAG,
+    IMPORT_TRANSPORT_KEY_TAG,
+];
+
+/// Highest suffix tried when renaming the tag of an imported seed, e.g. "tag#9"
+const MAX_IMPORT_TAG_SUFFIX: u32 = 9;
+
 struct LauncherLairClient {
     lair_client: LairClient,
 }
 
-pub enum Passphrase {
-    SecretString(SecretString),
-    PlainString(String),
-}
-
 impl LauncherLairClient {
     /// Connect to lair keystore
-    pub async fn new(connection_url: String, passphrase: String) -> Self {
-        let connection_url_parsed = Url::parse(connection_url.deref()).unwrap();
-        let passphrase_bufread: BufRead = passphrase.as_bytes().into();
+    pub async fn new(connection_url: String, passphrase: Passphrase) -> Result<Self> {
+        let connection_url_parsed = Url::parse(connection_url.deref()).map_err(|e| {
+            napi::Error::from_reason(format!("Failed to parse lair connection url: {}", e))
+        })?;
+        let passphrase_bufread = passphrase.to_buf_read()?;
 
         let lair_client = ipc_keystore_connect(connection_url_parsed, passphrase_bufread)
             .await
-            .unwrap();
+            .map_err(|e| napi::Error::from_reason(format!("Failed to connect to lair: {}", e)))?;
 
-        Self { lair_client }
+        Ok(Self { lair_client })
     }
 
     /// Sign a zome call
@@ -83,219 +98,509 @@ impl LauncherLairClient {
         }
     }
 
+    /// Returns the base64 encoded ed25519 public key of the seed with the given tag,
+    /// if lair holds such a seed
+    pub async fn get_seed_pub_key(&self, tag: String) -> Option<String> {
+        match self.lair_client.get_entry(tag.into()).await {
+            Ok(LairEntryInfo::Seed { seed_info, .. })
+            | Ok(LairEntryInfo::DeepLockedSeed { seed_info, .. }) => Some(
+                AgentPubKeyB64::from(AgentPubKey::from_raw_32(
+                    seed_info.ed25519_pub_key.as_ref().to_vec(),
+                ))
+                .to_string(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Unlocks the locked seed bundle and imports its seed into lair
     pub async fn import_locked_seed_bundle(
         &self,
         import_locked_seed_bundle: String,
-        passphrase: String,
+        secret: SeedBundleSecret,
         tag: String,
-    ) -> Result<String> {
-        let unlocked_seed_bundle = unlock_device_bundle(&import_locked_seed_bundle, passphrase)
+        options: Option<SeedImportOptions>,
+    ) -> Result<ImportedSeed> {
+        let SeedImportOptions {
+            exportable,
+            on_tag_conflict,
+        } = options.unwrap_or_default();
+
+        let unlocked_seed_bundle = unlock_device_bundle(&import_locked_seed_bundle, secret)
             .await
-            .map_err(|e| {
-                napi::Error::from_reason(format!("Failed to generate random seed: {}", e))
-            })?;
+            .map_err(SeedImportError::Unlock)?;
 
-        let encryption_key = self.get_or_create_seed("import-encryption-key").await?;
-        let decryption_key = self.get_or_create_seed("import-decryption-key").await?;
+        let tag = self
+            .resolve_import_tag(tag, on_tag_conflict.unwrap_or(TagConflictStrategy::Fail))
+            .await?;
 
-        let secret = SecretKey::from_bytes(&*unlocked_seed_bundle.get_seed().read_lock())
-            .map_err(|e| napi::Error::from_reason(format!("Failed to get seed: {:?}", e)))?;
+        Ok(self
+            .import_unlocked_seed(&unlocked_seed_bundle, tag, exportable.unwrap_or(false))
+            .await?)
+    }
 
-        // encrypt seed that shall be imported
-        let (nonce, cipher) = self
-            .lair_client
-            .crypto_box_xsalsa_by_pub_key(
-                encryption_key.x25519_pub_key.clone(),
-                decryption_key.x25519_pub_key.clone(),
-                None,
-                secret.as_ref().into(),
-            )
+    /// Returns the tag to import a seed under, given how to deal with an existing
+    /// lair entry under the requested tag
+    async fn resolve_import_tag(
+        &self,
+        tag: String,
+        on_tag_conflict: TagConflictStrategy,
+    ) -> std::result::Result<String, SeedImportError> {
+        if !self.seed_exists(tag.clone()).await {
+            return Ok(tag);
+        }
+
+        match on_tag_conflict {
+            TagConflictStrategy::Fail => Err(SeedImportError::TagExists(tag)),
+            TagConflictStrategy::Rename => {
+                for suffix in 2..=MAX_IMPORT_TAG_SUFFIX {
+                    let renamed_tag = format!("{}#{}", tag, suffix);
+                    if !self.seed_exists(renamed_tag.clone()).await {
+                        return Ok(renamed_tag);
+                    }
+                }
+                Err(SeedImportError::NoFreeTag(tag))
+            }
+        }
+    }
+
+    /// Imports the unlocked seed into lair under the given tag, which must not be
+    /// taken yet, and returns the public keys of the imported seed
+    async fn import_unlocked_seed(
+        &self,
+        unlocked_seed_bundle: &UnlockedSeedBundle,
+        tag: String,
+        exportable: bool,
+    ) -> std::result::Result<ImportedSeed, SeedImportError> {
+        let recipient_pub_key = self
+            .import_recipient_pub_key()
             .await
-            .map_err(|e| {
-                napi::Error::from_reason(format!("Failed to encrypt the device_bundle_seed: {}", e))
-            })?;
+            .map_err(SeedImportError::TransportKey)?;
+
+        let (sender_pub_key, nonce, cipher) =
+            encrypt_seed_for_import(unlocked_seed_bundle, recipient_pub_key.clone())
+                .await
+                .map_err(|e| SeedImportError::Encrypt(e.to_string()))?;
 
         // import the encrypted seed into lair
         let imported_seed = self
             .lair_client
             .import_seed(
-                encryption_key.x25519_pub_key,
-                decryption_key.x25519_pub_key,
+                sender_pub_key,
+                recipient_pub_key,
                 None,
                 nonce,
                 cipher,
-                tag.into(),
-                false,
+                tag.clone().into(),
+                exportable,
             )
             .await
-            .map_err(|e| {
-                napi::Error::from_reason(format!("Failed to import seed into lair: {}", e))
-            })?;
+            .map_err(|e| SeedImportError::Import(e.to_string()))?;
+
+        Ok(ImportedSeed {
+            tag,
+            ed25519_pub_key: AgentPubKeyB64::from(AgentPubKey::from_raw_32(
+                imported_seed.ed25519_pub_key.as_ref().to_vec(),
+            ))
+            .to_string(),
+            x25519_pub_key: base64::encode_config(
+                imported_seed.x25519_pub_key.as_ref(),
+                base64::URL_SAFE_NO_PAD,
+            ),
+            exportable,
+        })
+    }
+
+    /// Imports the device seed at the given index of a KeyFile into lair. If the key
+    /// file does not contain a device seed at that index, it is derived from the
+    /// device seeds seed.
+    ///
+    /// The public key of the seed is checked against the key file before and after
+    /// importing it.
+    pub async fn import_device_seed_from_key_file(
+        &self,
+        key_file_json: String,
+        passphrase: Passphrase,
+        device_index: u32,
+        tag: String,
+        options: Option<SeedImportOptions>,
+    ) -> Result<ImportedDeviceSeed> {
+        let SeedImportOptions {
+            exportable,
+            on_tag_conflict,
+        } = options.unwrap_or_default();
+        let exportable = exportable.unwrap_or(false);
+
+        let key_file = parse_key_file(key_file_json).await?;
+
+        // Resolve the tag before unlocking the seed, which is the expensive part
+        let tag = self
+            .resolve_import_tag(tag, on_tag_conflict.unwrap_or(TagConflictStrategy::Fail))
+            .await?;
+
+        let (device_seed, derivation_path, derived) =
+            match key_file.device_seeds.get(&device_index.to_string()) {
+                Some(encrypted_device_seed) => {
+                    let device_seed = unlock_key_file_seed(
+                        &encrypted_device_seed.into(),
+                        &passphrase,
+                        &format!("device seed {}", device_index),
+                    )
+                    .await?;
+                    (
+                        device_seed,
+                        encrypted_device_seed.derivation_path.clone(),
+                        false,
+                    )
+                }
+                None => {
+                    let device_seeds_seed = unlock_key_file_seed(
+                        &(&key_file.device_seeds_seed).into(),
+                        &passphrase,
+                        "device seeds seed",
+                    )
+                    .await?;
+                    let device_seed =
+                        device_seeds_seed.derive(device_index).await.map_err(|e| {
+                            napi::Error::from_reason(format!(
+                                "Failed to derive device seed {}: {}",
+                                device_index, e
+                            ))
+                        })?;
+                    let mut derivation_path = key_file.device_seeds_seed.derivation_path.clone();
+                    derivation_path.push(device_index);
+                    (device_seed, derivation_path, true)
+                }
+            };
 
-        let imported_pubkey_b64 = AgentPubKeyB64::from(AgentPubKey::from_raw_32(
-            imported_seed.ed25519_pub_key.as_ref().to_vec(),
-        ));
+        let expected_pub_key = agent_pub_key_b64(&device_seed);
+        let ImportedSeed {
+            tag,
+            ed25519_pub_key: agent_pub_key,
+            ..
+        } = self
+            .import_unlocked_seed(&device_seed, tag, exportable)
+            .await?;
+        if agent_pub_key != expected_pub_key {
+            return Err(napi::Error::from_reason(format!(
+                "The public key of the seed imported into lair does not match the device seed. Expected {} but got {}",
+                expected_pub_key, agent_pub_key
+            )));
+        }
 
-        Ok(imported_pubkey_b64.to_string())
+        Ok(ImportedDeviceSeed {
+            tag,
+            agent_pub_key,
+            exportable,
+            device_index,
+            derivation_path,
+            derived,
+        })
     }
 
-    /// Reads a json file containing the device bundle and the device derivation path,
-    /// then derives the device seed and from it a sub seed at index 1 whose base64 encoded
-    /// public part should equal the initial_host_pub_key field.
+    /// Reads a host config json file and imports its host seed into lair, see
+    /// import_host_config_seed
     pub async fn derive_and_import_seed_from_json_file(
         &self,
         path: String,
-        passphrase: Option<String>,
-    ) -> Result<String> {
+        passphrase_provider: PassphraseProvider,
+        strict: Option<bool>,
+    ) -> Result<HostSeedImport> {
         let json_string = std::fs::read_to_string(path)?;
-        let parsed_string: serde_json::Value = serde_json::from_str(&json_string)?;
-        let v3_config = parsed_string
-            .get("v3")
-            .ok_or(napi::Error::from_reason(
-                "No top-level v3 key found in the json file",
-            ))?
-            .as_object()
-            .ok_or(napi::Error::from_reason("The v3 key is not of type string"))?;
-
-        let device_bundle = v3_config
-            .get("device_bundle")
-            .ok_or(napi::Error::from_reason("device_bundle field not found"))?
-            .as_str()
-            .ok_or(napi::Error::from_reason(
-                "device_bundle value is not of type string",
-            ))?
-            .to_string();
-
-        println!("Got device_bundle: {}", device_bundle);
-        println!("Got passphrase: {:?}", passphrase);
-
-        // let initial_host_pub_key_b64 = v3_config
-        //     .get("initial_host_pub_key")
-        //     .ok_or(napi::Error::from_reason(
-        //         "initial_host_pub_key field not found",
-        //     ))?
-        //     .as_str()
-        //     .ok_or(napi::Error::from_reason(
-        //         "initial_host_pub_key value is not of type string",
-        //     ))?;
-
-        let passphrase_enum = match passphrase {
-            Some(p) => Passphrase::PlainString(p),
-            None => {
-                let secret_passphrase = if let Some(mut input) =
-                    PassphraseInput::with_default_binary()
-                {
-                    // pinentry binary is available!
-                    input
-                        .with_description("Enter Seed Bundle Passphrase")
-                        .with_prompt("Passphrase:")
-                        .interact()
-                        .map_err(|e| {
-                            napi::Error::from_reason(format!("Failed to collect passphrase: {e}"))
-                        })?
-                } else {
-                    return Err(napi::Error::from_reason(
-                        "No pinentry binary available to collect the passphrase.",
-                    ));
-                };
-
-                Passphrase::SecretString(secret_passphrase)
-            }
-        };
+        let host_config = parse_host_config(json_string)?;
 
-        // Unlock the device bundle and derive the sub seed at index 1 which whose public part should
-        // correspond to initial_host_pub_key_b64
-        let derived_key_pair =
-            derive_seed_from_device_bundle(&device_bundle, passphrase_enum, 1).await?;
+        self.import_host_config_seed(host_config, passphrase_provider, strict)
+            .await
+    }
 
-        let initial_host_pub_key_b64_derived = AgentPubKeyB64::from(AgentPubKey::from_raw_32(
-            derived_key_pair.public.as_ref().into(),
-        ));
+    /// Derives the seed at the device derivation path of a host config from its device
+    /// bundle, whose base64 encoded public part should equal the initial_host_pub_key
+    /// field, and imports it into lair.
+    ///
+    /// In strict mode, which is the default, the initial_host_pub_key field is required
+    /// and the seed is not imported if the derived public key does not match it.
+    pub async fn import_host_config_seed(
+        &self,
+        host_config: HostConfig,
+        passphrase_provider: PassphraseProvider,
+        strict: Option<bool>,
+    ) -> Result<HostSeedImport> {
+        let strict = strict.unwrap_or(true);
+        let HostConfig {
+            device_bundle,
+            device_derivation_path,
+            initial_host_pub_key: initial_host_pub_key_b64,
+            ..
+        } = host_config;
 
-        // if initial_host_pub_key_b64 != initial_host_pub_key_b64_derived.to_string() {
-        //     return Err(napi::Error::from_reason(format!("Derived public key does not match the expected public key. Expected {initial_host_pub_key_b64} but derived {initial_host_pub_key_b64_derived}")));
-        // }
+        if strict && initial_host_pub_key_b64.is_none() {
+            return Err(napi::Error::from_reason(
+                "initial_host_pub_key field not found",
+            ));
+        }
 
-        // Get or generate key for encryption of the seed
-        let encryption_key = self.get_or_create_seed("import-encryption-key").await?;
-        let decryption_key = self.get_or_create_seed("import-decryption-key").await?;
+        let passphrase = passphrase_provider
+            .passphrase("Enter Seed Bundle Passphrase")
+            .await?;
 
-        // encrypt seed that shall be imported
-        let (nonce, cipher) = self
-            .lair_client
-            .crypto_box_xsalsa_by_pub_key(
-                encryption_key.x25519_pub_key.clone(),
-                decryption_key.x25519_pub_key.clone(),
-                None,
-                derived_key_pair.secret.as_ref().into(),
-            )
-            .await
-            .map_err(|e| {
-                napi::Error::from_reason(format!("Failed to encrypt the device_bundle_seed: {}", e))
+        // Unlock the device bundle and derive the seed at the device derivation path whose
+        // public part should correspond to initial_host_pub_key_b64
+        let mut derived_seed =
+            unlock_device_bundle(&device_bundle, SeedBundleSecret::Passphrase(passphrase)).await?;
+        for index in &device_derivation_path {
+            derived_seed = derived_seed.derive(*index).await.map_err(|e| {
+                napi::Error::from_reason(format!(
+                    "Failed to derive seed at index {}: {:?}",
+                    index, e
+                ))
             })?;
+        }
 
-        let src_tag = format!("imported#{initial_host_pub_key_b64_derived}");
+        let derived_pub_key_b64 = agent_pub_key_b64(&derived_seed);
+        let pub_key_matches = initial_host_pub_key_b64
+            .as_ref()
+            .map(|expected| *expected == derived_pub_key_b64);
 
-        // import the encrypted seed into lair
-        let imported_seed = self
+        if strict && pub_key_matches == Some(false) {
+            return Err(napi::Error::from_reason(format!(
+                "Derived public key does not match the expected public key. Expected {} but derived {}",
+                initial_host_pub_key_b64.unwrap_or_default(),
+                derived_pub_key_b64
+            )));
+        }
+
+        let src_tag = format!("imported#{derived_pub_key_b64}");
+        let imported_pub_key_b64 = self
+            .import_unlocked_seed(&derived_seed, src_tag, false)
+            .await?
+            .ed25519_pub_key;
+
+        if imported_pub_key_b64 != derived_pub_key_b64 {
+            return Err(napi::Error::from_reason(format!(
+                "Imported public key does not match the derived public key. Expected {} but imported {}",
+                derived_pub_key_b64, imported_pub_key_b64
+            )));
+        }
+
+        Ok(HostSeedImport {
+            imported_pub_key: imported_pub_key_b64,
+            expected_pub_key: initial_host_pub_key_b64,
+            derived_pub_key: derived_pub_key_b64,
+            device_derivation_path,
+            pub_key_matches,
+        })
+    }
+
+    /// Returns the x25519 public key of the seed in lair that seeds to import are
+    /// encrypted for in transit, since lair can only decrypt imports for keys it holds
+    ///
+    /// Lair cannot delete entries, so exactly one such helper seed remains in the
+    /// keystore: the import-decryption-key of earlier versions if it exists, otherwise
+    /// the import-transport-key, which is created with the first import. Seeds of the
+    /// user are never used for this, so that their x25519 keys aren't involved in the
+    /// key agreement of imports.
+    async fn import_recipient_pub_key(&self) -> Result<X25519PubKey> {
+        for tag in [LEGACY_IMPORT_RECIPIENT_TAG, IMPORT_TRANSPORT_KEY_TAG] {
+            match self.lair_client.get_entry(tag.into()).await {
+                Ok(LairEntryInfo::Seed { seed_info, .. }) => return Ok(seed_info.x25519_pub_key),
+                Ok(_) => {
+                    return Err(napi::Error::from_reason(format!(
+                        "The lair entry {} is not a seed",
+                        tag
+                    )))
+                }
+                Err(_) => (),
+            }
+        }
+
+        let seed_info = self
             .lair_client
-            .import_seed(
-                encryption_key.x25519_pub_key,
-                decryption_key.x25519_pub_key,
-                None,
-                nonce,
-                cipher,
-                src_tag.clone().into(),
-                false,
-            )
+            .new_seed(IMPORT_TRANSPORT_KEY_TAG.into(), None, false)
             .await
             .map_err(|e| {
-                napi::Error::from_reason(format!("Failed to import cipher into lair: {}", e))
+                napi::Error::from_reason(format!(
+                    "Failed to create import transport key in lair: {}",
+                    e
+                ))
             })?;
+        Ok(seed_info.x25519_pub_key)
+    }
 
-        let imported_pubkey_b64 = AgentPubKeyB64::from(AgentPubKey::from_raw_32(
-            imported_seed.ed25519_pub_key.as_ref().to_vec(),
-        ));
+    /// Lists the seeds kept in lair only to encrypt imports, including the ones
+    /// earlier versions left behind, which lair cannot remove
+    pub async fn find_import_helper_entries(&self) -> Result<Vec<ImportHelperEntry>> {
+        let entries =
+            self.lair_client.list_entries().await.map_err(|e| {
+                napi::Error::from_reason(format!("Failed to list lair entries: {}", e))
+            })?;
 
-        println!(
-            "\n\n\n#### Imported pubkey: {}\n\n\n",
-            imported_pubkey_b64.to_string()
-        );
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                LairEntryInfo::Seed { tag, seed_info } if IMPORT_HELPER_TAGS.contains(&&*tag) => {
+                    Some(ImportHelperEntry {
+                        tag: tag.to_string(),
+                        ed25519_pub_key: AgentPubKeyB64::from(AgentPubKey::from_raw_32(
+                            seed_info.ed25519_pub_key.to_vec(),
+                        ))
+                        .to_string(),
+                    })
+                }
+                _ => None,
+            })
+            .collect())
+    }
+}
 
-        // if initial_host_pub_key_b64 != imported_pubkey_b64.to_string() {
-        //     return Err(napi::Error::from_reason(format!("Imported public key does not match the expected public key. Expected {initial_host_pub_key_b64} but imported {imported_pubkey_b64}")));
-        // }
+/// Device seed imported into lair from a KeyFile
+#[napi(object)]
+pub struct ImportedDeviceSeed {
+    /// The tag the seed has been imported under, which differs from the requested
+    /// tag if it has been renamed due to a conflict
+    pub tag: String,
+    /// Base64 encoded public key of the imported seed, to be used as agent public key
+    pub agent_pub_key: String,
+    pub exportable: bool,
+    pub device_index: u32,
+    /// Derivation indices leading from the root seed to the device seed
+    pub derivation_path: Vec<u32>,
+    /// Whether the device seed was not contained in the key file and has been
+    /// derived from the device seeds seed
+    pub derived: bool,
+}
+
+/// How to import a seed under a tag that is already taken in lair
+///
+/// There is no strategy overwriting the existing seed, since lair can neither replace
+/// nor delete entries.
+#[napi(string_enum = "lowercase")]
+pub enum TagConflictStrategy {
+    /// Fail the import
+    Fail,
+    /// Import the seed under the tag suffixed with the first free "#2" to "#9"
+    Rename,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct SeedImportOptions {
+    /// Whether lair allows exporting the imported seed again, defaults to false
+    pub exportable: Option<bool>,
+    /// Defaults to failing the import
+    pub on_tag_conflict: Option<TagConflictStrategy>,
+}
+
+/// A seed imported into lair
+#[napi(object)]
+pub struct ImportedSeed {
+    /// The tag the seed has been imported under, which differs from the requested
+    /// tag if it has been renamed due to a conflict
+    pub tag: String,
+    /// Base64 encoded ed25519 public key, to be used as agent public key
+    pub ed25519_pub_key: String,
+    /// URL safe base64 encoded x25519 public key
+    pub x25519_pub_key: String,
+    pub exportable: bool,
+}
+
+/// A seed kept in lair only to encrypt imported seeds
+#[napi(object)]
+pub struct ImportHelperEntry {
+    pub tag: String,
+    /// Base64 encoded ed25519 public key of the seed
+    pub ed25519_pub_key: String,
+}
 
-        Ok(imported_pubkey_b64.to_string())
+/// Codes of the ways importing a seed into lair can fail. The message of an import
+/// error starts with its code followed by a colon, e.g. "TAG_EXISTS: ...".
+#[napi(string_enum = "SCREAMING_SNAKE_CASE")]
+pub enum SeedImportErrorCode {
+    /// The seed bundle could not be unlocked, e.g. due to a wrong passphrase
+    Unlock,
+    /// The tag is already taken and the tag conflict strategy is to fail
+    TagExists,
+    /// The tag and all the tags to rename the seed to are already taken
+    NoFreeTag,
+    /// The seed imports are encrypted for in transit could not be created
+    TransportKey,
+    /// The seed could not be encrypted for the import
+    Encrypt,
+    /// Lair rejected the import
+    Import,
+}
+
+impl SeedImportErrorCode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SeedImportErrorCode::Unlock => "UNLOCK",
+            SeedImportErrorCode::TagExists => "TAG_EXISTS",
+            SeedImportErrorCode::NoFreeTag => "NO_FREE_TAG",
+            SeedImportErrorCode::TransportKey => "TRANSPORT_KEY",
+            SeedImportErrorCode::Encrypt => "ENCRYPT",
+            SeedImportErrorCode::Import => "IMPORT",
+        }
     }
+}
 
-    async fn get_or_create_seed(&self, tag: &str) -> Result<SeedInfo> {
-        // Get or generate key for encryption of the seed
-        let encryption_key = match self.lair_client.get_entry(tag.into()).await {
-            Ok(key) => match key {
-                LairEntryInfo::Seed { seed_info, .. } => seed_info,
-                _ => {
-                    return Err(napi::Error::from_reason(
-                        "The import encryption key in lair is of the wrong format.",
-                    ))
-                }
-            },
-            Err(_) => self
-                .lair_client
-                .new_seed(tag.into(), None, false)
-                .await
-                .map_err(|e| {
-                    napi::Error::from_reason(format!(
-                        "Failed to create new import encryption key in lair: {}",
-                        e
-                    ))
-                })?,
-        };
+/// Ways importing a seed into lair can fail
+enum SeedImportError {
+    Unlock(napi::Error),
+    TagExists(String),
+    NoFreeTag(String),
+    TransportKey(napi::Error),
+    Encrypt(String),
+    Import(String),
+}
 
-        Ok(encryption_key)
+impl SeedImportError {
+    fn code(&self) -> SeedImportErrorCode {
+        match self {
+            SeedImportError::Unlock(_) => SeedImportErrorCode::Unlock,
+            SeedImportError::TagExists(_) => SeedImportErrorCode::TagExists,
+            SeedImportError::NoFreeTag(_) => SeedImportErrorCode::NoFreeTag,
+            SeedImportError::TransportKey(_) => SeedImportErrorCode::TransportKey,
+            SeedImportError::Encrypt(_) => SeedImportErrorCode::Encrypt,
+            SeedImportError::Import(_) => SeedImportErrorCode::Import,
+        }
+    }
+}
+
+impl From<SeedImportError> for napi::Error {
+    fn from(error: SeedImportError) -> Self {
+        let code = error.code();
+        let reason = match error {
+            SeedImportError::Unlock(e) => format!("Failed to unlock seed bundle: {}", e.reason),
+            SeedImportError::TagExists(tag) => {
+                format!("A seed with tag {} already exists in lair", tag)
+            }
+            SeedImportError::NoFreeTag(tag) => format!(
+                "A seed with tag {} already exists in lair and no free tag to rename it to was found",
+                tag
+            ),
+            SeedImportError::TransportKey(e) => {
+                format!("Failed to get import encryption key: {}", e.reason)
+            }
+            SeedImportError::Encrypt(e) => format!("Failed to encrypt seed for import: {}", e),
+            SeedImportError::Import(e) => format!("Failed to import seed into lair: {}", e),
+        };
+        napi::Error::from_reason(format!("{}: {}", code.as_str(), reason))
     }
 }
 
+/// Result of deriving and importing the host seed of a host config json file
+#[napi(object)]
+pub struct HostSeedImport {
+    /// Base64 encoded public key of the seed imported into lair
+    pub imported_pub_key: String,
+    /// The initial_host_pub_key of the json file, if it contains one
+    pub expected_pub_key: Option<String>,
+    pub derived_pub_key: String,
+    /// Derivation indices leading from the device bundle to the imported seed
+    pub device_derivation_path: Vec<u32>,
+    /// Whether the derived public key matches the expected one, if there is one
+    pub pub_key_matches: Option<bool>,
+}
+
 #[napi(js_name = "LauncherLairClient")]
 pub struct JsLauncherLairClient {
     launcher_lair_client: Option<LauncherLairClient>,
@@ -311,12 +616,15 @@ impl JsLauncherLairClient {
     }
 
     #[napi]
-    pub async fn connect(connection_url: String, passphrase: String) -> Self {
-        let launcher_lair_client = LauncherLairClient::new(connection_url, passphrase).await;
+    pub async fn connect(
+        connection_url: String,
+        passphrase: Passphrase,
+    ) -> Result<JsLauncherLairClient> {
+        let launcher_lair_client = LauncherLairClient::new(connection_url, passphrase).await?;
 
-        JsLauncherLairClient {
+        Ok(JsLauncherLairClient {
             launcher_lair_client: Some(launcher_lair_client),
-        }
+        })
     }
 
     #[napi]
@@ -340,17 +648,73 @@ impl JsLauncherLairClient {
             .await
     }
 
+    #[napi]
+    pub async fn get_seed_pub_key(&self, tag: String) -> Option<String> {
+        self.launcher_lair_client
+            .as_ref()
+            .unwrap()
+            .get_seed_pub_key(tag)
+            .await
+    }
+
     #[napi]
     pub async fn import_locked_seed_bundle(
         &self,
         import_locked_seed_bundle: String,
-        passphrase: String,
+        passphrase: Passphrase,
+        tag: String,
+        options: Option<SeedImportOptions>,
+    ) -> Result<ImportedSeed> {
+        self.launcher_lair_client
+            .as_ref()
+            .unwrap()
+            .import_locked_seed_bundle(
+                import_locked_seed_bundle,
+                SeedBundleSecret::Passphrase(passphrase),
+                tag,
+                options,
+            )
+            .await
+    }
+
+    #[napi]
+    pub async fn import_locked_seed_bundle_with_security_answers(
+        &self,
+        import_locked_seed_bundle: String,
+        security_answers: Vec<Passphrase>,
         tag: String,
-    ) -> Result<String> {
+        options: Option<SeedImportOptions>,
+    ) -> Result<ImportedSeed> {
+        let secret = SeedBundleSecret::from_security_answers(security_answers)?;
         self.launcher_lair_client
             .as_ref()
             .unwrap()
-            .import_locked_seed_bundle(import_locked_seed_bundle, passphrase, tag)
+            .import_locked_seed_bundle(import_locked_seed_bundle, secret, tag, options)
+            .await
+    }
+
+    #[napi]
+    pub async fn find_import_helper_entries(&self) -> Result<Vec<ImportHelperEntry>> {
+        self.launcher_lair_client
+            .as_ref()
+            .unwrap()
+            .find_import_helper_entries()
+            .await
+    }
+
+    #[napi]
+    pub async fn import_device_seed_from_key_file(
+        &self,
+        key_file_json: String,
+        passphrase: Passphrase,
+        device_index: u32,
+        tag: String,
+        options: Option<SeedImportOptions>,
+    ) -> Result<ImportedDeviceSeed> {
+        self.launcher_lair_client
+            .as_ref()
+            .unwrap()
+            .import_device_seed_from_key_file(key_file_json, passphrase, device_index, tag, options)
             .await
     }
 
@@ -358,91 +722,531 @@ impl JsLauncherLairClient {
     pub async fn derive_and_import_seed_from_json_file(
         &self,
         path: String,
-        passphrase: Option<String>,
-    ) -> Result<String> {
+        passphrase: Option<Passphrase>,
+        strict: Option<bool>,
+        passphrase_source: Option<PassphraseSource>,
+        passphrase_callback: Option<PassphraseCallback>,
+    ) -> Result<HostSeedImport> {
+        let passphrase_provider =
+            PassphraseProvider::from_args(passphrase, passphrase_source, passphrase_callback)?;
+        self.launcher_lair_client
+            .as_ref()
+            .unwrap()
+            .derive_and_import_seed_from_json_file(path, passphrase_provider, strict)
+            .await
+    }
+
+    #[napi]
+    pub async fn import_host_config_seed(
+        &self,
+        host_config: HostConfig,
+        passphrase: Option<Passphrase>,
+        strict: Option<bool>,
+        passphrase_source: Option<PassphraseSource>,
+        passphrase_callback: Option<PassphraseCallback>,
+    ) -> Result<HostSeedImport> {
+        let passphrase_provider =
+            PassphraseProvider::from_args(passphrase, passphrase_source, passphrase_callback)?;
         self.launcher_lair_client
             .as_ref()
             .unwrap()
-            .derive_and_import_seed_from_json_file(path, passphrase)
+            .import_host_config_seed(host_config, passphrase_provider, strict)
             .await
     }
 }
 
-/// Unlocks the device bundle seed with the given passphrase, then derives
-/// a sub SeedBundle by the given index and returns the corresponding derived KeyPair
-pub async fn derive_seed_from_device_bundle(
-    device_bundle: &String,
-    passphrase: Passphrase,
-    index: u32,
-) -> napi::Result<Keypair> {
-    let locked_bundle =
-        base64::decode_config(device_bundle, base64::URL_SAFE_NO_PAD).map_err(|e| {
-            napi::Error::from_reason(format!("Failed to decode device bundle: {:?}", e))
-        })?;
-    match UnlockedSeedBundle::from_locked(&locked_bundle)
+/// Secret material that can unlock one of the ciphers of a locked seed bundle
+pub enum SeedBundleSecret {
+    Passphrase(Passphrase),
+    SecurityAnswers(Passphrase, Passphrase, Passphrase),
+}
+
+impl SeedBundleSecret {
+    /// Builds a SeedBundleSecret from the answers to the three security questions
+    /// of a bundle, in the order the questions are stored in the bundle
+    pub fn from_security_answers(answers: Vec<Passphrase>) -> napi::Result<Self> {
+        match <[Passphrase; 3]>::try_from(answers) {
+            Ok([a1, a2, a3]) => Ok(SeedBundleSecret::SecurityAnswers(a1, a2, a3)),
+            Err(answers) => Err(napi::Error::from_reason(format!(
+                "Expected exactly 3 security answers but got {}",
+                answers.len()
+            ))),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            SeedBundleSecret::Passphrase(_) => "passphrase",
+            SeedBundleSecret::SecurityAnswers(..) => "security answers",
+        }
+    }
+}
+
+fn cipher_type_name(cipher: &LockedSeedCipher) -> String {
+    match cipher {
+        LockedSeedCipher::PwHash(_) => "PwHash".to_string(),
+        LockedSeedCipher::SecurityQuestions(_) => "SecurityQuestions".to_string(),
+        LockedSeedCipher::UnsupportedCipher(name) => format!("Unsupported({})", name),
+        _ => "Unknown".to_string(),
+    }
+}
+
+/// Encrypts the seed for the recipient key in lair with a throwaway sender keypair,
+/// as expected by lair's import_seed, and returns the sender public key, the nonce
+/// and the cipher
+async fn encrypt_seed_for_import(
+    unlocked_seed_bundle: &UnlockedSeedBundle,
+    recipient_pub_key: X25519PubKey,
+) -> std::result::Result<(X25519PubKey, [u8; 24], Arc<[u8]>), OneErr> {
+    use sodoken::crypto_box::curve25519xsalsa20poly1305 as crypto_box;
+
+    let sender_pub_key = sodoken::BufWriteSized::<32>::new_no_lock();
+    let sender_sec_key = sodoken::BufWriteSized::<32>::new_mem_locked()?;
+    crypto_box::keypair(sender_pub_key.clone(), sender_sec_key.clone()).await?;
+
+    let nonce = sodoken::BufWriteSized::<24>::new_no_lock();
+    sodoken::random::bytes_buf(nonce.clone()).await?;
+
+    let cipher = crypto_box::easy(
+        nonce.clone(),
+        unlocked_seed_bundle.get_seed().to_read_unsized(),
+        recipient_pub_key.cloned_inner(),
+        sender_sec_key,
+    )
+    .await?;
+
+    let sender_pub_key = X25519PubKey::from(*sender_pub_key.read_lock_sized());
+    let nonce = *nonce.read_lock_sized();
+    let cipher: Arc<[u8]> = cipher.read_lock().to_vec().into();
+
+    Ok((sender_pub_key, nonce, cipher))
+}
+
+fn decode_locked_bundle(device_bundle: &str) -> napi::Result<Vec<u8>> {
+    base64::decode_config(device_bundle, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| napi::Error::from_reason(format!("Failed to decode device bundle: {:?}", e)))
+}
+
+/// Tries every cipher of the locked seed bundle that matches the kind of the given
+/// secret and returns the first one that unlocks successfully.
+///
+/// If none of them can be unlocked, the error lists the cipher types that were found
+/// in the bundle.
+pub async fn unlock_seed_bundle(
+    locked_bundle: &[u8],
+    secret: SeedBundleSecret,
+) -> napi::Result<UnlockedSeedBundle> {
+    let ciphers = UnlockedSeedBundle::from_locked(locked_bundle)
         .await
         .map_err(|e| {
             napi::Error::from_reason(format!("Failed to create UnlockedSeedBundle: {:?}", e))
-        })?
-        .remove(0)
-    {
-        LockedSeedCipher::PwHash(bundle) => {
-            let passphrase_bufread = match passphrase {
-                Passphrase::SecretString(s) => BufRead::from(s.expose_secret().as_bytes()),
-                Passphrase::PlainString(s) => BufRead::from(s.as_bytes()),
-            };
-            let seed = bundle.unlock(passphrase_bufread).await.map_err(|e| {
-                napi::Error::from_reason(format!(
-                    "Failed to unlock cipher with the given passphrase: {:?}",
-                    e
-                ))
-            })?;
-            let derived_seed = seed.derive(index).await.unwrap();
-
-            Ok(Keypair {
-                public: PublicKey::from_bytes(&*derived_seed.get_sign_pub_key().read_lock())
-                    .map_err(|e| {
-                        napi::Error::from_reason(format!("Failed to get public key: {:?}", e))
-                    })?,
-                secret: SecretKey::from_bytes(&*derived_seed.get_seed().read_lock()).map_err(
-                    |e| napi::Error::from_reason(format!("Failed to get seed: {:?}", e)),
-                )?,
-            })
+        })?;
+
+    let mut found_ciphers = Vec::new();
+    let mut unlock_errors = Vec::new();
+
+    for cipher in ciphers {
+        found_ciphers.push(cipher_type_name(&cipher));
+        let unlock_result = match (cipher, &secret) {
+            (LockedSeedCipher::PwHash(bundle), SeedBundleSecret::Passphrase(passphrase)) => {
+                bundle.unlock(passphrase.to_buf_read()?).await
+            }
+            (
+                LockedSeedCipher::SecurityQuestions(bundle),
+                SeedBundleSecret::SecurityAnswers(a1, a2, a3),
+            ) => {
+                let answers = (a1.to_buf_read()?, a2.to_buf_read()?, a3.to_buf_read()?);
+                bundle.unlock(answers).await
+            }
+            _ => continue,
+        };
+        match unlock_result {
+            Ok(seed) => return Ok(seed),
+            Err(e) => unlock_errors.push(format!("{:?}", e)),
         }
-        _ => Err(napi::Error::from_reason(
-            "Unsupported LockedSeedCipher variant".to_string(),
-        )),
     }
+
+    if unlock_errors.is_empty() {
+        return Err(napi::Error::from_reason(format!(
+            "No cipher in the seed bundle can be unlocked with {}. Ciphers found: [{}]",
+            secret.kind(),
+            found_ciphers.join(", ")
+        )));
+    }
+
+    Err(napi::Error::from_reason(format!(
+        "Failed to unlock cipher with the given {}: {}. Ciphers found: [{}]",
+        secret.kind(),
+        unlock_errors.join("; "),
+        found_ciphers.join(", ")
+    )))
 }
 
-pub async fn unlock_device_bundle(
-    device_bundle: &String,
-    passphrase: String,
-) -> napi::Result<UnlockedSeedBundle> {
-    let locked_bundle =
-        base64::decode_config(device_bundle, base64::URL_SAFE_NO_PAD).map_err(|e| {
-            napi::Error::from_reason(format!("Failed to decode device bundle: {:?}", e))
-        })?;
-    match UnlockedSeedBundle::from_locked(&locked_bundle)
+/// Returns the three security questions of the first security questions cipher
+/// of the given base64 encoded locked seed bundle, if it contains one.
+#[napi]
+pub async fn get_security_questions(device_bundle: String) -> napi::Result<Option<Vec<String>>> {
+    let locked_bundle = decode_locked_bundle(&device_bundle)?;
+    let ciphers = UnlockedSeedBundle::from_locked(&locked_bundle)
         .await
         .map_err(|e| {
             napi::Error::from_reason(format!("Failed to create UnlockedSeedBundle: {:?}", e))
-        })?
-        .remove(0)
-    {
-        LockedSeedCipher::PwHash(bundle) => {
-            let passphrase_bufread = BufRead::from(passphrase.as_bytes());
-            let seed = bundle.unlock(passphrase_bufread).await.map_err(|e| {
-                napi::Error::from_reason(format!(
-                    "Failed to unlock cipher with the given passphrase: {:?}",
-                    e
-                ))
-            })?;
+        })?;
 
-            Ok(seed)
+    Ok(ciphers.into_iter().find_map(|cipher| match cipher {
+        LockedSeedCipher::SecurityQuestions(bundle) => {
+            let (q1, q2, q3) = bundle.get_question_list().clone();
+            Some(vec![q1, q2, q3])
         }
-        _ => Err(napi::Error::from_reason(
-            "Unsupported LockedSeedCipher variant".to_string(),
-        )),
+        _ => None,
+    }))
+}
+
+/// Unlocks the device bundle seed with the given secret, then derives
+/// a sub SeedBundle by the given index and returns the corresponding derived KeyPair
+pub async fn derive_seed_from_device_bundle(
+    device_bundle: &str,
+    secret: SeedBundleSecret,
+    index: u32,
+) -> napi::Result<Keypair> {
+    let seed = unlock_device_bundle(device_bundle, secret).await?;
+    let derived_seed = seed.derive(index).await.map_err(|e| {
+        napi::Error::from_reason(format!("Failed to derive seed at index {}: {:?}", index, e))
+    })?;
+
+    Ok(Keypair {
+        public: PublicKey::from_bytes(&derived_seed.get_sign_pub_key().read_lock())
+            .map_err(|e| napi::Error::from_reason(format!("Failed to get public key: {:?}", e)))?,
+        secret: SecretKey::from_bytes(&derived_seed.get_seed().read_lock())
+            .map_err(|e| napi::Error::from_reason(format!("Failed to get seed: {:?}", e)))?,
+    })
+}
+
+pub async fn unlock_device_bundle(
+    device_bundle: &str,
+    secret: SeedBundleSecret,
+) -> napi::Result<UnlockedSeedBundle> {
+    let locked_bundle = decode_locked_bundle(device_bundle)?;
+    unlock_seed_bundle(&locked_bundle, secret).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::{
+        key_file_from_root_seed, serialize_key_file, KeyGenerationOptions, PwHashLimitsPreset,
+    };
+    use hc_seed_bundle::PwHashLimits;
+    use lair_keystore_api::{
+        config::LairServerConfigInner, in_proc_keystore::InProcKeystore,
+        mem_store::create_mem_store_factory,
+    };
+    use napi::bindgen_prelude::block_on;
+
+    async fn in_proc_lair_client() -> LauncherLairClient {
+        let passphrase = sodoken::BufRead::from(b"passphrase".to_vec());
+        let root_path = std::env::temp_dir().join(format!("lair-test-{}", nanoid::nanoid!()));
+        std::fs::create_dir_all(&root_path).unwrap();
+        let config = Arc::new(
+            PwHashLimits::Minimum
+                .with_exec(|| LairServerConfigInner::new(&root_path, passphrase.clone()))
+                .await
+                .unwrap(),
+        );
+        let keystore = InProcKeystore::new(config, create_mem_store_factory(), passphrase)
+            .await
+            .unwrap();
+        // The in-process keystore keeps its store in memory
+        std::fs::remove_dir_all(&root_path).unwrap();
+
+        LauncherLairClient {
+            lair_client: keystore.new_client().await.unwrap(),
+        }
+    }
+
+    #[test]
+    fn locked_seed_bundles_are_imported_through_a_single_transport_key() {
+        block_on(async {
+            let client = in_proc_lair_client().await;
+            let existing_seed = client
+                .lair_client
+                .new_seed("existing".into(), None, false)
+                .await
+                .unwrap();
+
+            let passphrase = Passphrase::new("passphrase".to_string());
+            let lock_random_seed = || async {
+                let seed = UnlockedSeedBundle::new_random().await.unwrap();
+                let locked_bundle = PwHashLimits::Minimum
+                    .with_exec(|| {
+                        seed.lock()
+                            .add_pwhash_cipher(passphrase.to_buf_read().unwrap())
+                    })
+                    .lock()
+                    .await
+                    .unwrap();
+                (
+                    agent_pub_key_b64(&seed),
+                    base64::encode_config(locked_bundle, base64::URL_SAFE_NO_PAD),
+                )
+            };
+            let import = |locked_bundle: String, options: SeedImportOptions| {
+                client.import_locked_seed_bundle(
+                    locked_bundle,
+                    SeedBundleSecret::Passphrase(passphrase.clone()),
+                    "device-seed".to_string(),
+                    Some(options),
+                )
+            };
+
+            let (pub_key, locked_bundle) = lock_random_seed().await;
+            let imported_seed = import(locked_bundle, SeedImportOptions::default())
+                .await
+                .unwrap();
+            assert_eq!(imported_seed.tag, "device-seed");
+            assert_eq!(imported_seed.ed25519_pub_key, pub_key);
+            assert!(!imported_seed.exportable);
+
+            let (other_pub_key, other_locked_bundle) = lock_random_seed().await;
+            let error = import(other_locked_bundle.clone(), SeedImportOptions::default())
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.reason,
+                "TAG_EXISTS: A seed with tag device-seed already exists in lair"
+            );
+
+            let renamed_seed = import(
+                other_locked_bundle,
+                SeedImportOptions {
+                    exportable: Some(true),
+                    on_tag_conflict: Some(TagConflictStrategy::Rename),
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(renamed_seed.tag, "device-seed#2");
+            assert_eq!(renamed_seed.ed25519_pub_key, other_pub_key);
+            assert!(renamed_seed.exportable);
+
+            // Both imports have been encrypted for the same transport key, which is
+            // the only helper seed in lair
+            let tags: Vec<String> = client
+                .lair_client
+                .list_entries()
+                .await
+                .unwrap()
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    LairEntryInfo::Seed { tag, .. } => Some(tag.to_string()),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(tags.len(), 4);
+            assert!(tags.contains(&"existing".to_string()));
+            assert!(tags.contains(&IMPORT_TRANSPORT_KEY_TAG.to_string()));
+            let helper_tags: Vec<String> = client
+                .find_import_helper_entries()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.tag)
+                .collect();
+            assert_eq!(helper_tags, vec![IMPORT_TRANSPORT_KEY_TAG.to_string()]);
+            assert_eq!(
+                client.get_seed_pub_key("existing".to_string()).await,
+                Some(
+                    AgentPubKeyB64::from(AgentPubKey::from_raw_32(
+                        existing_seed.ed25519_pub_key.to_vec()
+                    ))
+                    .to_string()
+                )
+            );
+        })
+    }
+
+    #[test]
+    fn seeds_are_imported_through_the_decryption_key_of_earlier_versions() {
+        block_on(async {
+            let client = in_proc_lair_client().await;
+            let legacy_recipient = client
+                .lair_client
+                .new_seed(LEGACY_IMPORT_RECIPIENT_TAG.into(), None, false)
+                .await
+                .unwrap();
+            assert_eq!(
+                client.import_recipient_pub_key().await.unwrap(),
+                legacy_recipient.x25519_pub_key
+            );
+
+            let passphrase = Passphrase::new("passphrase".to_string());
+            let seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let locked_bundle = PwHashLimits::Minimum
+                .with_exec(|| {
+                    seed.lock()
+                        .add_pwhash_cipher(passphrase.to_buf_read().unwrap())
+                })
+                .lock()
+                .await
+                .unwrap();
+            let imported_seed = client
+                .import_locked_seed_bundle(
+                    base64::encode_config(locked_bundle, base64::URL_SAFE_NO_PAD),
+                    SeedBundleSecret::Passphrase(passphrase),
+                    "device-seed".to_string(),
+                    None,
+                )
+                .await
+                .unwrap();
+            assert_eq!(imported_seed.ed25519_pub_key, agent_pub_key_b64(&seed));
+
+            // No transport key has been created next to the existing helper seed
+            let helper_tags: Vec<String> = client
+                .find_import_helper_entries()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.tag)
+                .collect();
+            assert_eq!(helper_tags, vec![LEGACY_IMPORT_RECIPIENT_TAG.to_string()]);
+        })
+    }
+
+    #[test]
+    fn seed_import_errors_start_with_their_code() {
+        block_on(async {
+            let client = in_proc_lair_client().await;
+            let passphrase = Passphrase::new("passphrase".to_string());
+            let seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let locked_bundle = base64::encode_config(
+                PwHashLimits::Minimum
+                    .with_exec(|| {
+                        seed.lock()
+                            .add_pwhash_cipher(passphrase.to_buf_read().unwrap())
+                    })
+                    .lock()
+                    .await
+                    .unwrap(),
+                base64::URL_SAFE_NO_PAD,
+            );
+            let import = |secret: Passphrase, on_tag_conflict: TagConflictStrategy| {
+                client.import_locked_seed_bundle(
+                    locked_bundle.clone(),
+                    SeedBundleSecret::Passphrase(secret),
+                    "device-seed".to_string(),
+                    Some(SeedImportOptions {
+                        exportable: None,
+                        on_tag_conflict: Some(on_tag_conflict),
+                    }),
+                )
+            };
+
+            let error = import(
+                Passphrase::new("wrong".to_string()),
+                TagConflictStrategy::Fail,
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(error.reason.starts_with("UNLOCK: "));
+
+            client
+                .lair_client
+                .new_seed("device-seed".into(), None, false)
+                .await
+                .unwrap();
+            for suffix in 2..=MAX_IMPORT_TAG_SUFFIX {
+                client
+                    .lair_client
+                    .new_seed(format!("device-seed#{}", suffix).into(), None, false)
+                    .await
+                    .unwrap();
+            }
+            let error = import(passphrase.clone(), TagConflictStrategy::Rename)
+                .await
+                .err()
+                .unwrap();
+            assert!(error.reason.starts_with("NO_FREE_TAG: "));
+
+            // The codes are distinct and match the values of the exported enum
+            let codes = [
+                SeedImportErrorCode::Unlock,
+                SeedImportErrorCode::TagExists,
+                SeedImportErrorCode::NoFreeTag,
+                SeedImportErrorCode::TransportKey,
+                SeedImportErrorCode::Encrypt,
+                SeedImportErrorCode::Import,
+            ]
+            .map(|code| code.as_str());
+            assert_eq!(
+                codes,
+                [
+                    "UNLOCK",
+                    "TAG_EXISTS",
+                    "NO_FREE_TAG",
+                    "TRANSPORT_KEY",
+                    "ENCRYPT",
+                    "IMPORT"
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn device_seed_import_from_key_file_resolves_tag_conflicts() {
+        block_on(async {
+            let client = in_proc_lair_client().await;
+            let passphrase = Passphrase::new("passphrase".to_string());
+            let key_file = key_file_from_root_seed(
+                UnlockedSeedBundle::new_random().await.unwrap(),
+                passphrase.clone(),
+                Some(KeyGenerationOptions {
+                    derivation: None,
+                    passphrases: None,
+                    security_questions: None,
+                    pw_hash_limits: Some(PwHashLimitsPreset::Interactive),
+                }),
+                |_| (),
+            )
+            .await
+            .unwrap();
+            let key_file_json = serialize_key_file(key_file).await.unwrap();
+            let import = |device_index: u32, options: Option<SeedImportOptions>| {
+                client.import_device_seed_from_key_file(
+                    key_file_json.clone(),
+                    passphrase.clone(),
+                    device_index,
+                    "device-seed".to_string(),
+                    options,
+                )
+            };
+
+            let imported_seed = import(0, None).await.unwrap();
+            assert_eq!(imported_seed.tag, "device-seed");
+            assert!(!imported_seed.derived);
+
+            let error = import(1, None).await.err().unwrap();
+            assert_eq!(
+                error.reason,
+                "TAG_EXISTS: A seed with tag device-seed already exists in lair"
+            );
+
+            let renamed_seed = import(
+                1,
+                Some(SeedImportOptions {
+                    exportable: None,
+                    on_tag_conflict: Some(TagConflictStrategy::Rename),
+                }),
+            )
+            .await
+            .unwrap();
+            assert_eq!(renamed_seed.tag, "device-seed#2");
+            assert!(renamed_seed.derived);
+            assert_eq!(
+                client.get_seed_pub_key("device-seed#2".to_string()).await,
+                Some(renamed_seed.agent_pub_key)
+            );
+        })
     }
 }
@@ -1,9 +1,13 @@
 #[macro_use]
 extern crate napi_derive;
 
+pub mod bundle_store;
 pub mod conductor_config;
 pub mod decode_webapp;
+pub mod host_config;
 pub mod key_generation;
+pub mod key_revocation;
+pub mod passphrase;
 pub mod types;
 mod utils;
 pub mod launcher_lair_client;
@@ -0,0 +1,420 @@
+use hc_seed_bundle::dependencies::sodoken;
+use napi::bindgen_prelude::{
+    Either, FromNapiValue, Promise, TypeName, ValidateNapiValue, ValueType,
+};
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction};
+use pinentry::PassphraseInput;
+use secrecy::{ExposeSecret, SecretString};
+
+/// A passphrase or another secret entered by the user, like a security answer
+///
+/// The secret is zeroized on drop and copied into memory locked buffers only where
+/// it is handed to sodoken. Debug output is redacted and there is no Display
+/// implementation, so the secret can't end up in logs or error messages by accident.
+#[derive(Clone)]
+pub struct Passphrase(SecretString);
+
+impl Passphrase {
+    pub fn new(passphrase: String) -> Self {
+        Passphrase(SecretString::new(passphrase))
+    }
+
+    pub(crate) fn expose_secret(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expose_secret().is_empty()
+    }
+
+    /// Copies the passphrase into a memory locked sodoken buffer
+    pub(crate) fn to_buf_read(&self) -> napi::Result<sodoken::BufRead> {
+        let bytes = self.expose_secret().as_bytes();
+        let buf = sodoken::BufWrite::new_mem_locked(bytes.len()).map_err(|e| {
+            napi::Error::from_reason(format!(
+                "Failed to allocate secure memory for passphrase: {}",
+                e
+            ))
+        })?;
+        buf.write_lock().copy_from_slice(bytes);
+        Ok(buf.to_read())
+    }
+}
+
+impl From<String> for Passphrase {
+    fn from(passphrase: String) -> Self {
+        Passphrase::new(passphrase)
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase([REDACTED])")
+    }
+}
+
+// Passphrases are taken from JS as plain strings and wrapped right away
+impl TypeName for Passphrase {
+    fn type_name() -> &'static str {
+        "String"
+    }
+
+    fn value_type() -> ValueType {
+        ValueType::String
+    }
+}
+
+impl ValidateNapiValue for Passphrase {}
+
+impl FromNapiValue for Passphrase {
+    unsafe fn from_napi_value(
+        env: napi::sys::napi_env,
+        napi_val: napi::sys::napi_value,
+    ) -> napi::Result<Self> {
+        String::from_napi_value(env, napi_val).map(Passphrase::new)
+    }
+}
+
+/// Where to collect a seed bundle passphrase from if it is not passed directly
+#[napi(string_enum = "lowercase")]
+pub enum PassphraseSourceKind {
+    /// The passphrase callback, which is passed the description of the passphrase
+    /// to collect and returns it, optionally as a promise
+    Callback,
+    /// The environment variable named by envVar
+    Env,
+    /// The first line read from the file descriptor fd, which is left open. The
+    /// standard streams 0, 1 and 2 are not accepted.
+    Fd,
+    /// An interactive pinentry prompt
+    Pinentry,
+}
+
+#[napi(object)]
+pub struct PassphraseSource {
+    pub kind: PassphraseSourceKind,
+    pub env_var: Option<String>,
+    pub fd: Option<i32>,
+}
+
+/// Callback collecting a passphrase from JS, see PassphraseSourceKind::Callback
+pub type PassphraseCallback = ThreadsafeFunction<String, ErrorStrategy::Fatal>;
+
+/// Collects a seed bundle passphrase from one of the supported sources
+pub enum PassphraseProvider {
+    Explicit(Passphrase),
+    Callback(PassphraseCallback),
+    EnvVar(String),
+    FileDescriptor(i32),
+    Pinentry,
+}
+
+impl PassphraseProvider {
+    /// Picks the provider for the arguments of a napi function taking an optional
+    /// passphrase, passphrase source and passphrase callback. An explicit passphrase
+    /// takes precedence, and pinentry is used if none of them is given.
+    pub fn from_args(
+        passphrase: Option<Passphrase>,
+        source: Option<PassphraseSource>,
+        callback: Option<PassphraseCallback>,
+    ) -> napi::Result<Self> {
+        if let Some(passphrase) = passphrase {
+            return Ok(PassphraseProvider::Explicit(passphrase));
+        }
+
+        let source = match source {
+            Some(source) => source,
+            None => {
+                return Ok(callback
+                    .map(PassphraseProvider::Callback)
+                    .unwrap_or(PassphraseProvider::Pinentry))
+            }
+        };
+
+        match source.kind {
+            PassphraseSourceKind::Callback => {
+                callback
+                    .map(PassphraseProvider::Callback)
+                    .ok_or(napi::Error::from_reason(
+                        "Passphrase source is callback but no passphrase callback was passed",
+                    ))
+            }
+            PassphraseSourceKind::Env => {
+                source
+                    .env_var
+                    .map(PassphraseProvider::EnvVar)
+                    .ok_or(napi::Error::from_reason(
+                        "Passphrase source is env but no envVar was passed",
+                    ))
+            }
+            PassphraseSourceKind::Fd => {
+                source
+                    .fd
+                    .map(PassphraseProvider::FileDescriptor)
+                    .ok_or(napi::Error::from_reason(
+                        "Passphrase source is fd but no fd was passed",
+                    ))
+            }
+            PassphraseSourceKind::Pinentry => Ok(PassphraseProvider::Pinentry),
+        }
+    }
+
+    /// Collects the passphrase, describing it as `description` to the user where
+    /// the source is interactive
+    pub async fn passphrase(self, description: &str) -> napi::Result<Passphrase> {
+        match self {
+            PassphraseProvider::Explicit(passphrase) => Ok(passphrase),
+            PassphraseProvider::Callback(callback) => {
+                passphrase_from_callback(callback, description).await
+            }
+            PassphraseProvider::EnvVar(name) => passphrase_from_env_var(&name),
+            PassphraseProvider::FileDescriptor(fd) => passphrase_from_fd(fd),
+            PassphraseProvider::Pinentry => passphrase_from_pinentry(description),
+        }
+    }
+}
+
+async fn passphrase_from_callback(
+    callback: PassphraseCallback,
+    description: &str,
+) -> napi::Result<Passphrase> {
+    let passphrase = match callback
+        .call_async::<Either<String, Promise<String>>>(description.to_string())
+        .await
+        .map_err(|e| {
+            napi::Error::from_reason(format!("Passphrase callback failed: {}", e.reason))
+        })? {
+        Either::A(passphrase) => passphrase,
+        Either::B(promise) => promise.await.map_err(|e| {
+            napi::Error::from_reason(format!(
+                "Passphrase callback promise was rejected: {}",
+                e.reason
+            ))
+        })?,
+    };
+
+    non_empty(
+        Passphrase::new(passphrase),
+        "Passphrase callback returned an empty passphrase",
+    )
+}
+
+fn passphrase_from_env_var(name: &str) -> napi::Result<Passphrase> {
+    let passphrase = std::env::var(name).map_err(|e| {
+        napi::Error::from_reason(format!(
+            "Failed to read passphrase from environment variable {}: {}",
+            name, e
+        ))
+    })?;
+
+    non_empty(
+        Passphrase::new(passphrase),
+        &format!("Environment variable {} holds an empty passphrase", name),
+    )
+}
+
+/// Reads the first line of the file descriptor, which stays open and owned by the caller
+///
+/// The standard streams are rejected since reading the passphrase from them would
+/// consume input meant for the process itself. The line is read byte by byte from a
+/// duplicate of the file descriptor, so nothing past the line break is consumed and
+/// the passphrase is only ever held in buffers that are zeroized on drop.
+#[cfg(unix)]
+fn passphrase_from_fd(fd: i32) -> napi::Result<Passphrase> {
+    use secrecy::zeroize::{Zeroize, Zeroizing};
+    use std::io::{ErrorKind, Read};
+    use std::os::unix::io::BorrowedFd;
+
+    if fd < 0 {
+        return Err(napi::Error::from_reason(format!(
+            "Invalid passphrase file descriptor {}",
+            fd
+        )));
+    }
+    if fd <= 2 {
+        return Err(napi::Error::from_reason(format!(
+            "Passphrase file descriptor {} is a standard stream, pass a file descriptor opened for the passphrase instead",
+            fd
+        )));
+    }
+
+    // Safety: the borrow only lives for the duplication below, which fails with EBADF
+    // if the file descriptor is not open. The duplicate is closed when it is dropped,
+    // the caller's file descriptor is left untouched.
+    let mut file = std::fs::File::from(
+        unsafe { BorrowedFd::borrow_raw(fd) }
+            .try_clone_to_owned()
+            .map_err(|e| {
+                napi::Error::from_reason(format!(
+                    "Failed to open passphrase file descriptor {}: {}",
+                    fd, e
+                ))
+            })?,
+    );
+
+    let mut passphrase = Zeroizing::new(Vec::with_capacity(256));
+    let mut byte = Zeroizing::new([0u8; 1]);
+    loop {
+        match file.read(&mut byte[..]) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => {
+                // Grow into a new buffer by hand, since a reallocation would leave the
+                // old one behind without zeroizing it
+                if passphrase.len() == passphrase.capacity() {
+                    let mut grown = Zeroizing::new(Vec::with_capacity(passphrase.capacity() * 2));
+                    grown.extend_from_slice(&passphrase);
+                    passphrase = grown;
+                }
+                passphrase.push(byte[0]);
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(napi::Error::from_reason(format!(
+                    "Failed to read passphrase from file descriptor {}: {}",
+                    fd, e
+                )))
+            }
+        }
+    }
+    if passphrase.last() == Some(&b'\r') {
+        passphrase.pop();
+    }
+
+    let passphrase = String::from_utf8(std::mem::take(&mut *passphrase)).map_err(|e| {
+        e.into_bytes().zeroize();
+        napi::Error::from_reason(format!(
+            "File descriptor {} holds a passphrase that is not valid UTF-8",
+            fd
+        ))
+    })?;
+
+    non_empty(
+        Passphrase::new(passphrase),
+        &format!("File descriptor {} holds an empty passphrase", fd),
+    )
+}
+
+#[cfg(not(unix))]
+fn passphrase_from_fd(_fd: i32) -> napi::Result<Passphrase> {
+    Err(napi::Error::from_reason(
+        "Reading the passphrase from a file descriptor is only supported on unix",
+    ))
+}
+
+fn passphrase_from_pinentry(description: &str) -> napi::Result<Passphrase> {
+    let mut input = PassphraseInput::with_default_binary().ok_or(napi::Error::from_reason(
+        "No pinentry binary available to collect the passphrase.",
+    ))?;
+
+    input
+        .with_description(description)
+        .with_prompt("Passphrase:")
+        .interact()
+        .map(Passphrase)
+        .map_err(|e| {
+            napi::Error::from_reason(format!("Pinentry failed to collect passphrase: {e}"))
+        })
+}
+
+fn non_empty(passphrase: Passphrase, error: &str) -> napi::Result<Passphrase> {
+    if passphrase.is_empty() {
+        return Err(napi::Error::from_reason(error));
+    }
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::launcher_lair_client::{unlock_seed_bundle, SeedBundleSecret};
+    use hc_seed_bundle::{PwHashLimits, UnlockedSeedBundle};
+    use napi::bindgen_prelude::block_on;
+
+    #[test]
+    fn secrets_never_appear_in_debug_output_or_unlock_errors() {
+        block_on(async {
+            let passphrase = Passphrase::new("correct horse".to_string());
+            assert!(!format!("{:?}", passphrase).contains("correct horse"));
+
+            let seed = UnlockedSeedBundle::new_random().await.unwrap();
+            let cipher_builder = PwHashLimits::Minimum.with_exec(|| {
+                let answer = || Passphrase::new("correct horse".to_string());
+                seed.lock()
+                    .add_pwhash_cipher(passphrase.to_buf_read().unwrap())
+                    .add_security_question_cipher(
+                        ("q1".to_string(), "q2".to_string(), "q3".to_string()),
+                        (
+                            answer().to_buf_read().unwrap(),
+                            answer().to_buf_read().unwrap(),
+                            answer().to_buf_read().unwrap(),
+                        ),
+                    )
+            });
+            let locked_bundle = cipher_builder.lock().await.unwrap();
+
+            let wrong_secret = || Passphrase::new("battery staple".to_string());
+            let secrets = [
+                SeedBundleSecret::Passphrase(wrong_secret()),
+                SeedBundleSecret::from_security_answers(vec![
+                    wrong_secret(),
+                    wrong_secret(),
+                    wrong_secret(),
+                ])
+                .unwrap(),
+            ];
+            for secret in secrets {
+                let error = unlock_seed_bundle(&locked_bundle, secret)
+                    .await
+                    .err()
+                    .unwrap();
+                assert!(!error.reason.contains("battery staple"));
+                assert!(!error.reason.contains("correct horse"));
+            }
+        })
+    }
+
+    #[test]
+    fn env_var_passphrase_is_read_and_missing_var_is_reported() {
+        std::env::set_var("LAUNCHER_TEST_PASSPHRASE", "correct horse");
+        let passphrase = passphrase_from_env_var("LAUNCHER_TEST_PASSPHRASE").unwrap();
+        assert_eq!(passphrase.expose_secret(), "correct horse");
+
+        let error = passphrase_from_env_var("LAUNCHER_TEST_PASSPHRASE_UNSET").unwrap_err();
+        assert!(error.reason.contains("LAUNCHER_TEST_PASSPHRASE_UNSET"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fd_passphrase_is_read_up_to_the_first_line_break() {
+        use std::io::{Read, Write};
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+        use std::os::unix::net::UnixStream;
+
+        let (mut writer, reader) = UnixStream::pair().unwrap();
+        writer
+            .write_all(b"correct horse\nbattery staple\n")
+            .unwrap();
+        drop(writer);
+
+        let fd = reader.into_raw_fd();
+        let passphrase = passphrase_from_fd(fd).unwrap();
+        assert_eq!(passphrase.expose_secret(), "correct horse");
+
+        // The file descriptor is left open for the caller to close, with the input
+        // after the line break still unread
+        let mut reader = unsafe { UnixStream::from_raw_fd(fd) };
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "battery staple\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn standard_streams_are_rejected_as_passphrase_fd() {
+        for fd in 0..=2 {
+            let error = passphrase_from_fd(fd).unwrap_err();
+            assert!(error.reason.contains("is a standard stream"));
+        }
+    }
+}
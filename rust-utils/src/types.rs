@@ -3,6 +3,8 @@ use holo_hash::{AgentPubKey, DnaHash};
 use holochain_integrity_types::{FunctionName, ZomeName};
 use holochain_zome_types::prelude::{CapSecret, CellId, ExternIO, ZomeCallUnsigned};
 use kitsune_p2p_timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Clone)]
 #[napi(object)]
@@ -55,4 +57,115 @@ pub struct ZomeCallNapi {
 pub struct HappAndUiBytes {
     pub happ_bytes: Vec<u8>,
     pub ui_bytes: Option<Vec<u8>>,
-}
\ No newline at end of file
+}
+
+/// Locations and sha256 hashes of a happ or webhapp file decoded to disk
+#[napi(object)]
+pub struct HappAndUiPaths {
+    pub happ_path: String,
+    /// Hex encoded sha256 hash of the .happ file
+    pub happ_sha256: String,
+    /// Directory the UI assets were unzipped to, if the file is a webhapp
+    pub ui_path: Option<String>,
+    /// Hex encoded sha256 hash of the ui.zip the UI assets were unzipped from
+    pub ui_zip_sha256: Option<String>,
+}
+
+/// Hashes of a UI as it was extracted from its ui.zip
+#[napi(object)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UiAssetManifest {
+    /// Hex encoded sha256 hash of the ui.zip
+    pub ui_zip_sha256: String,
+    /// Hex encoded sha256 hashes of the extracted files, keyed by their path relative to the
+    /// UI directory with `/` as the path separator on every platform
+    pub files: HashMap<String, String>,
+}
+
+/// Files of a UI directory that differ from its UiAssetManifest
+#[napi(object)]
+pub struct UiDirectoryReport {
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+/// Contents of a happ or webhapp file, as shown to the user before installing it
+#[napi(object)]
+pub struct BundleInspection {
+    pub name: String,
+    pub description: Option<String>,
+    pub roles: Vec<RoleInspection>,
+    /// Locations of the happ and the UI inside the webhapp, if the file is a webhapp
+    pub webhapp: Option<WebhappLocations>,
+}
+
+/// Locations of the happ and the UI of a webhapp. Bundled resources are given by their
+/// path inside the webhapp, other locations by their local path or url.
+#[napi(object)]
+pub struct WebhappLocations {
+    pub happ_location: String,
+    pub ui_location: String,
+}
+
+/// If, how and when a cell is created for a role, see CellProvisioning of holochain_types
+#[napi(string_enum = "snake_case")]
+pub enum CellProvisioningStrategy {
+    Create,
+    UseExisting,
+    CloneOnly,
+}
+
+#[napi(object)]
+pub struct RoleInspection {
+    pub name: String,
+    pub provisioning: CellProvisioningStrategy,
+    /// Whether cell creation is deferred, only set for the create strategy
+    pub deferred: Option<bool>,
+    /// Whether the depended-upon app is protected, only set for the use_existing strategy
+    pub protected: Option<bool>,
+    pub clone_limit: u32,
+    /// DNA hash the DNA of the role is expected to have before modifiers are applied
+    pub installed_hash: Option<String>,
+    /// The DNA of the role, if it is bundled into the happ
+    pub dna: Option<DnaInspection>,
+}
+
+#[napi(object)]
+pub struct DnaInspection {
+    pub name: String,
+    pub network_seed: String,
+    /// DNA properties, decoded to json
+    pub properties: serde_json::Value,
+    /// Origin time in microseconds since the unix epoch
+    pub origin_time: i64,
+    /// Hash of the DNA with the modifiers of the role applied
+    pub dna_hash: String,
+    pub integrity_zomes: Vec<ZomeInspection>,
+    pub coordinator_zomes: Vec<ZomeInspection>,
+}
+
+#[napi(object)]
+pub struct ZomeInspection {
+    pub name: String,
+    pub wasm_hash: String,
+}
+
+/// Modifiers overriding those a role's DNA is given in the happ manifest
+#[napi(object)]
+pub struct DnaModifiersOverride {
+    pub network_seed: Option<String>,
+    /// DNA properties as json, replacing the properties of the manifest as a whole
+    pub properties: Option<serde_json::Value>,
+    /// Origin time in microseconds since the unix epoch
+    pub origin_time: Option<i64>,
+}
+
+#[napi(object)]
+pub struct RoleDnaHash {
+    pub role_name: String,
+    /// Hash of the DNA a cell of the role would be created with, if the role does not use
+    /// an existing cell and its DNA is bundled into the happ
+    pub dna_hash: Option<String>,
+}
@@ -2,3 +2,13 @@ pub fn vec_to_arr<T, const N: usize>(v: Vec<T>) -> [T; N] {
     v.try_into()
         .unwrap_or_else(|v: Vec<T>| panic!("Expected a Vec of length {} but it was {}", N, v.len()))
 }
+
+/// Runs blocking file IO or hashing on the blocking thread pool of the napi tokio runtime,
+/// so that async napi functions don't stall its worker threads
+pub async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> napi::Result<T> + Send + 'static,
+) -> napi::Result<T> {
+    napi::bindgen_prelude::spawn_blocking(f)
+        .await
+        .map_err(|e| napi::Error::from_reason(format!("Blocking task failed: {}", e)))?
+}
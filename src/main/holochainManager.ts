@@ -1,4 +1,3 @@
-import { platform } from '@electron-toolkit/utils';
 import type {
   ActionHashB64,
   AgentPubKey,
@@ -10,9 +9,7 @@ import type {
   MembraneProof,
 } from '@holochain/client';
 import { AdminWebsocket, decodeHashFromBase64, encodeHashToBase64 } from '@holochain/client';
-import AdmZip from 'adm-zip';
 import * as childProcess from 'child_process';
-import crypto from 'crypto';
 import { app, session } from 'electron';
 import fs from 'fs';
 import getPort from 'get-port';
@@ -75,6 +72,7 @@ export class HolochainManager {
   integrityChecker: IntegrityChecker;
   version: HolochainVersion;
   holochainDataRoot: HolochainDataRoot;
+  bundleStore: rustUtils.BundleStore;
   appTokens: Record<InstalledAppId, AppAuthenticationToken> = {};
 
   constructor(
@@ -99,6 +97,9 @@ export class HolochainManager {
     this.installedApps = installedApps;
     this.version = version;
     this.holochainDataRoot = holochainDataRoot;
+    this.bundleStore = new rustUtils.BundleStore(
+      launcherFileSystem.holochainDataBase(holochainDataRoot),
+    );
   }
 
   static async launch(
@@ -362,8 +363,8 @@ export class HolochainManager {
   }) {
     if (!happAndUiBytes.uiBytes) throw new Error('UI bytes undefined.');
 
-    const happSha256 = this.storeHapp(happAndUiBytes.happBytes);
-    const uiZipSha256 = this.storeUiIfNecessary(happAndUiBytes.uiBytes, icon);
+    const happSha256 = await this.storeHapp(happAndUiBytes.happBytes);
+    const uiZipSha256 = await this.storeUiIfNecessary(happAndUiBytes.uiBytes, icon);
 
     await this.installWebhappFromHashes({
       agentPubKey,
@@ -392,10 +393,34 @@ export class HolochainManager {
     agentPubKey?: AgentPubKeyB64;
   }) {
     // write [sha256].happ to happs directory
-    const happHasher = crypto.createHash('sha256');
-    const happSha256 = happHasher.update(Buffer.from(happBytes)).digest('hex');
-    const happFilePath = path.join(this.fs.happsDir(this.holochainDataRoot), `${happSha256}.happ`);
-    writeFile(happFilePath, Buffer.from(happBytes));
+    const happSha256 = await this.storeHapp(happBytes);
+
+    await this.installHeadlessHappFromHash({
+      happSha256,
+      appId,
+      distributionInfo,
+      networkSeed,
+      membrane_proofs,
+      agentPubKey,
+    });
+  }
+
+  async installHeadlessHappFromHash({
+    happSha256,
+    appId,
+    distributionInfo,
+    networkSeed,
+    membrane_proofs,
+    agentPubKey,
+  }: {
+    happSha256: string;
+    appId: string;
+    distributionInfo: DistributionInfoV1;
+    networkSeed?: string;
+    membrane_proofs?: { [key: string]: MembraneProof };
+    agentPubKey?: AgentPubKeyB64;
+  }) {
+    const happFilePath = this.happFilePath(happSha256);
 
     let pubKey: AgentPubKey;
 
@@ -413,6 +438,7 @@ export class HolochainManager {
       path: happFilePath,
       network_seed: networkSeed,
     });
+    await this.bundleStore.setReferences(appId, { happSha256 });
 
     // store app metadata to installed app directory
     const metaData: AppMetadata<AppMetadataV1> = {
@@ -471,10 +497,10 @@ export class HolochainManager {
     membrane_proofs?: { [key: string]: MembraneProof };
     agentPubKey?: AgentPubKeyB64;
   }): Promise<void> {
-    if (!this.isUiAvailable(uiZipSha256)) {
+    if (!(await this.isUiAvailable(uiZipSha256))) {
       throw new Error('UI not found for this hash. UI needs to be stored from bytes first.');
     }
-    if (!this.isHappAvailableAndValid(happSha256)) {
+    if (!(await this.isHappAvailableAndValid(happSha256))) {
       throw new Error(
         'happ file not found for this hash or its sha256 is invalid. happ file needs to be stored from bytes first.',
       );
@@ -502,6 +528,7 @@ export class HolochainManager {
       path: this.happFilePath(happSha256),
       network_seed: networkSeed,
     });
+    await this.bundleStore.setReferences(appId, { happSha256, uiZipSha256 });
 
     // store app metadata to installed app directory
     const metaData: AppMetadata<AppMetadataV1> = {
@@ -595,7 +622,7 @@ export class HolochainManager {
     appId: string,
     appVersionActionHash?: ActionHashB64,
   ): Promise<void> {
-    if (!this.isUiAvailable(uiZipSha256)) {
+    if (!(await this.isUiAvailable(uiZipSha256))) {
       throw new Error('UI not found for this hash. UI needs to be stored from bytes first.');
     }
 
@@ -662,6 +689,10 @@ export class HolochainManager {
       default:
         throw new Error('Invalid existing distribution info type.');
     }
+    await this.bundleStore.setReferences(appId, {
+      happSha256: metadata.data.happ.sha256,
+      uiZipSha256,
+    });
     this.launcherEmitter.emit(REFETCH_DATA_IN_ALL_WINDOWS, `update-${appId}`);
   }
 
@@ -670,30 +701,44 @@ export class HolochainManager {
    * @param happBytes
    * @returns
    */
-  storeHapp(happBytes: Array<number>): string {
-    const happHasher = crypto.createHash('sha256');
-    const happSha256 = happHasher.update(Buffer.from(happBytes)).digest('hex');
-    const happFilePath = this.happFilePath(happSha256);
-    writeFile(happFilePath, Buffer.from(happBytes));
-    return happSha256;
+  async storeHapp(happBytes: Array<number>): Promise<string> {
+    return this.bundleStore.putHappBytes(happBytes);
   }
 
-  private createUiDirectory(uiDir: string, uiBytes: Array<number>): void {
-    fs.mkdirSync(uiDir, { recursive: true });
-
-    const hashes: Record<string, string> = {};
-    const zip = new AdmZip(Buffer.from(uiBytes));
-    zip.getEntries().forEach((entry) => {
-      const hash = crypto.createHash('sha256').update(entry.getData()).digest('hex');
-      const relativeFilePath = platform.isWindows
-        ? entry.entryName.replaceAll('/', '\\')
-        : entry.entryName;
-      hashes[relativeFilePath] = hash;
-    });
-
-    this.integrityChecker.storeToSignedJSON(path.join(uiDir, 'hashes.json'), hashes);
-    zip.extractAllTo(path.join(uiDir, 'assets'));
+  /**
+   * Decodes a .happ or .webhapp file from disk and stores its happ and, for webhapps, its UI
+   * without reading the file into memory.
+   * If an icon is provided, it is stored alongside the UI assets.
+   *
+   * @param filePath - Path to the .happ or .webhapp file.
+   * @param icon - Optional icon to be stored with the UI assets.
+   * @returns The sha256 hashes of the .happ file and of the ui.zip, if there is a UI.
+   */
+  async storeHappOrWebhappFile(
+    filePath: string,
+    icon?: Uint8Array,
+  ): Promise<{ happSha256: string; uiZipSha256?: string }> {
+    // decoded next to the happs and uis directories, so that the UI can be moved into place
+    const holochainDataBase = this.fs.holochainDataBase(this.holochainDataRoot);
+    createDirIfNotExists(holochainDataBase);
+    const decodeDir = fs.mkdtempSync(path.join(holochainDataBase, '.partial-decode-'));
+    try {
+      const { happPath, uiPath, uiZipSha256 } = await rustUtils.decodeHappOrWebhappToPaths(
+        filePath,
+        path.join(decodeDir, 'app.happ'),
+        path.join(decodeDir, 'ui'),
+      );
+      const happSha256 = await this.bundleStore.putHappFile(happPath);
+      if (!uiPath || !uiZipSha256) return { happSha256 };
+
+      const uiAssetManifest = await this.bundleStore.putUiDirectory(uiZipSha256, uiPath);
+      this.storeUiMetadata(uiAssetManifest, icon);
+      return { happSha256, uiZipSha256 };
+    } finally {
+      fs.rmSync(decodeDir, { recursive: true, force: true });
+    }
   }
+
   private handleIconStorage(uiDir: string, icon?: Uint8Array): void {
     const storeIcon = (iconBytes: Uint8Array) => {
       const iconPath = path.join(uiDir, 'icon.png');
@@ -732,24 +777,59 @@ export class HolochainManager {
    * @param icon - Optional icon to be stored with the UI assets.
    * @returns The sha256 hash of the UI zip bytes.
    */
-  storeUiIfNecessary(uiBytes: Array<number>, icon?: Uint8Array): string {
+  async storeUiIfNecessary(uiBytes: Array<number>, icon?: Uint8Array): Promise<string> {
     if (icon && !(icon instanceof Uint8Array)) throw new Error('Icon must be of type Uint8Array.');
 
-    // compute UI hash
-    const uiZipHasher = crypto.createHash('sha256');
-    const uiZipSha256 = uiZipHasher.update(Buffer.from(uiBytes)).digest('hex');
+    // the bundle store only extracts the UI if no valid copy of it is stored yet
+    const uiAssetManifest = await this.bundleStore.putUiBytes(uiBytes);
+    this.storeUiMetadata(uiAssetManifest, icon);
 
-    const uiDir = path.join(this.fs.uisDir(this.holochainDataRoot), uiZipSha256);
-    const assetsPath = path.join(uiDir, 'assets');
+    return uiAssetManifest.uiZipSha256;
+  }
 
-    // here we assume that if the uiDir exists, the hashes.json exists as well.
-    if (!fs.existsSync(assetsPath)) {
-      this.createUiDirectory(uiDir, uiBytes);
-    }
+  private storeUiMetadata(uiAssetManifest: rustUtils.UiAssetManifest, icon?: Uint8Array): void {
+    const uiDir = path.join(this.fs.uisDir(this.holochainDataRoot), uiAssetManifest.uiZipSha256);
+
+    // The bundle store keys files with '/' on every platform while hashes.json files are
+    // keyed by platform specific paths
+    const uiHashes: UiHashes = {};
+    Object.entries(uiAssetManifest.files).forEach(([key, hash]) => {
+      uiHashes[key.split('/').join(path.sep)] = hash;
+    });
+    this.integrityChecker.storeToSignedJSON<UiHashes>(path.join(uiDir, 'hashes.json'), uiHashes);
 
     this.handleIconStorage(uiDir, icon);
+  }
 
-    return uiZipSha256;
+  /**
+   * Seeds the references of the bundle store from the metadata of all installed apps, so that
+   * happs and UIs of apps installed before the bundle store recorded references are kept
+   * when collecting garbage
+   */
+  async seedBundleStoreReferences(): Promise<void> {
+    const references: Record<string, rustUtils.AppReferences> = {};
+    this.installedApps.forEach((appInfo) => {
+      const appId = appInfo.installed_app_id;
+      const metadataPath = path.join(
+        this.fs.appMetadataDir(appId, this.holochainDataRoot),
+        'info.json',
+      );
+      if (!fs.existsSync(metadataPath)) {
+        console.warn(`No metadata found for app ${appId}, its happ and UI are not referenced.`);
+        return;
+      }
+      const metadata =
+        this.integrityChecker.readSignedJSON<AppMetadata<AppMetadataV1>>(metadataPath);
+      const uiLocation = metadata.data.ui?.location;
+      references[appId] = {
+        happSha256: metadata.data.happ.sha256,
+        uiZipSha256:
+          metadata.data.type === 'webhapp' && uiLocation?.type === 'filesystem'
+            ? uiLocation.sha256
+            : undefined,
+      };
+    });
+    await this.bundleStore.seedReferences(references);
   }
 
   happFilePath(happSha256: string): string {
@@ -763,16 +843,15 @@ export class HolochainManager {
     const partition = happSessionName(this.holochainDataRoot.name, appId);
     const ses = session.fromPartition(partition);
     await ses.clearStorageData();
+    // The .happ file and UI directory are kept until the next garbage collection of the
+    // bundle store, in case the same happ and UI get installed again
+    await this.bundleStore.removeReferences(appId);
 
     console.log('Uninstalled app.');
     const installedApps = await this.adminWebsocket.listApps({});
     console.log('Installed apps: ', installedApps);
     this.installedApps = installedApps;
     this.launcherEmitter.emit(REFETCH_DATA_IN_ALL_WINDOWS, `uninstall-${appId}`);
-    // TODO Potentially remove .happ file and ui directory in case it is not used by any
-    // other app instance anymore. This would mean however that happ and UI would need
-    // to be fetched over the network again if the same happ and UI shall be installed
-    // at a later time
   }
 
   async enableApp(appId: string) {
@@ -795,10 +874,8 @@ export class HolochainManager {
    *
    * @param sha256Ui sha256 hash of the zipped UI assets
    */
-  isUiAvailable(uiZipSha256: string): boolean {
-    const uiDir = path.join(this.fs.uisDir(this.holochainDataRoot), uiZipSha256);
-    // here we assume that if the uiDir exists, the hashes.json exists as well.
-    return fs.existsSync(path.join(uiDir, 'assets'));
+  async isUiAvailable(uiZipSha256: string): Promise<boolean> {
+    return this.bundleStore.hasUi(uiZipSha256, true);
   }
 
   /**
@@ -807,25 +884,18 @@ export class HolochainManager {
    *
    * @param happSha256 sha256 of the .happ file
    */
-  isHappAvailableAndValid(happSha256: string): boolean {
-    const happFilePath = this.happFilePath(happSha256);
-    if (!fs.existsSync(happFilePath)) {
-      return false;
+  async isHappAvailableAndValid(happSha256: string): Promise<boolean> {
+    if (await this.bundleStore.hasHapp(happSha256, true)) {
+      return true;
     }
-    const happBytes = fs.readFileSync(happFilePath);
-    const happHasher = crypto.createHash('sha256');
-    const happSha256Actual = happHasher.update(Buffer.from(happBytes)).digest('hex');
-    if (happSha256Actual !== happSha256) {
+    if (await this.bundleStore.hasHapp(happSha256, false)) {
       this.launcherEmitter.emit(
         'launcher-error',
-        `Found corrupted .happ file on disk. Expected sha256: ${happSha256}. Actual sha256: ${happSha256Actual}. The corrupted file will likely get overwritten with a valid one.`,
+        `Found corrupted .happ file on disk. Expected sha256: ${happSha256}. The corrupted file will likely get overwritten with a valid one.`,
       );
-      console.warn(
-        `Found corrupted .happ file on disk. Expected sha256: ${happSha256}. Actual sha256: ${happSha256Actual}`,
-      );
-      return false;
+      console.warn(`Found corrupted .happ file on disk. Expected sha256: ${happSha256}`);
     }
-    return true;
+    return false;
   }
 
   appIcon(appId: string): Uint8Array | undefined {
@@ -871,9 +941,3 @@ export class HolochainManager {
     return response.token;
   }
 }
-
-function writeFile(filePath: string, contents: string | NodeJS.ArrayBufferView): void {
-  const dirname = path.dirname(filePath);
-  fs.mkdirSync(dirname, { recursive: true });
-  fs.writeFileSync(filePath, contents);
-}
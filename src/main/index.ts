@@ -83,6 +83,7 @@ import {
   getInstalledAppsInfo,
   handleInstallError,
   installApp,
+  installAppFromPath,
   isDevhubInstalled,
   processHeadlessAppInstallation,
   signZomeCall,
@@ -250,6 +251,13 @@ app.whenReady().then(async () => {
 
   console.log('BEING RUN IN __dirnmane: ', __dirname);
 
+  // remove temporary .happ files left behind by earlier runs
+  try {
+    rustUtils.cleanupStaleTempHapps();
+  } catch (e) {
+    console.warn('Failed to clean up temporary happ files: ', e);
+  }
+
   PRIVILEGED_LAUNCHER_WINDOWS = setupAppWindows(LAUNCHER_EMITTER);
 
   const mainWindow = PRIVILEGED_LAUNCHER_WINDOWS[MAIN_WINDOW];
@@ -410,6 +418,16 @@ async function launchLairIfNecessary(password: string): Promise<void> {
     DEFAULT_LAIR_CLIENT = await rustUtils.LauncherLairClient.connect(LAIR_URL, password);
     LAIR_HANDLE = lairHandle;
   }
+
+  const importHelperEntries = await DEFAULT_LAIR_CLIENT!.findImportHelperEntries();
+  if (importHelperEntries.length > 0) {
+    LAUNCHER_EMITTER.emit(
+      LAUNCHER_LOG,
+      `Lair contains seeds only used to encrypt imported seeds: ${importHelperEntries
+        .map((entry) => entry.tag)
+        .join(', ')}`,
+    );
+  }
 }
 
 /**
@@ -445,20 +463,26 @@ async function handleQuickSetup(password: string): Promise<void> {
   if (!dpkiDeviceSeedExists) {
     LAUNCHER_EMITTER.emit(LOADING_PROGRESS_UPDATE, 'generatingKeyRecoveryFile');
     // 3.1. Generate KeyFile
-    const keyFile = await rustUtils.generateInitialSeeds(password);
+    const keyFile = await rustUtils.generateInitialSeeds(password, undefined, (_err, progress) => {
+      if (progress.finished) {
+        LAUNCHER_EMITTER.emit(
+          LAUNCHER_LOG,
+          `Encrypted ${progress.seedName} (${progress.encryptedCount}/${progress.totalCount})`,
+        );
+      }
+    });
+    const keyFileJson = await rustUtils.serializeKeyFile(keyFile);
     // 3.2. Import device seed into lair
     LAUNCHER_EMITTER.emit(LOADING_PROGRESS_UPDATE, 'importingDeviceSeed');
-    const importedSeedPubkey64 = await DEFAULT_LAIR_CLIENT!.importLockedSeedBundle(
-      keyFile.deviceSeed0,
-      password,
-      DEVICE_SEED_LAIR_TAG,
-    );
+    const { agentPubKey: importedSeedPubkey64 } =
+      await DEFAULT_LAIR_CLIENT!.importDeviceSeedFromKeyFile(
+        keyFileJson,
+        password,
+        0,
+        DEVICE_SEED_LAIR_TAG,
+      );
     // 3.3. Store to key recovery file on disk
-    fs.writeFileSync(
-      LAUNCHER_FILE_SYSTEM.keyRecoveryFilePath,
-      JSON.stringify(keyFile, undefined, 2),
-      'utf-8',
-    );
+    fs.writeFileSync(LAUNCHER_FILE_SYSTEM.keyRecoveryFilePath, keyFileJson, 'utf-8');
     console.log('Imported device seed with pubkey: ', importedSeedPubkey64);
     LAUNCHER_EMITTER.emit(
       LAUNCHER_LOG,
@@ -497,7 +521,7 @@ async function handleAdvancedSetup(
     throw new Error('DPKI_DEVICE_SEED already exists in lair. Cannot import new device seed.');
 
   LAUNCHER_EMITTER.emit(LOADING_PROGRESS_UPDATE, 'importingDeviceSeed');
-  const importedSeedPubkey64 = await DEFAULT_LAIR_CLIENT!.importLockedSeedBundle(
+  const { ed25519PubKey: importedSeedPubkey64 } = await DEFAULT_LAIR_CLIENT!.importLockedSeedBundle(
     lockedDeviceSeed,
     lockedSeedPassphrase,
     DEVICE_SEED_LAIR_TAG,
@@ -554,6 +578,7 @@ async function launchHolochain(password: string, lairUrl: string): Promise<void>
   DEFAULT_HOLOCHAIN_DATA_ROOT = holochainDataRoot;
   HOLOCHAIN_MANAGERS[holochainDataRoot.name] = holochainManager;
   APP_PORT = holochainManager.appPort;
+  await holochainManager.seedBundleStoreReferences();
   // Install default apps if necessary
   // TODO check sha256 hashes
 
@@ -704,8 +729,10 @@ const router = t.router({
     const holochainManager = getHolochainManager(DEFAULT_HOLOCHAIN_DATA_ROOT!.name);
     const appstoreAppClient = await getAppstoreAppClient();
 
-    const isUiAvailable = holochainManager.isUiAvailable(appVersionEntry.bundle_hashes.ui_hash);
-    const isHappAvailable = holochainManager.isHappAvailableAndValid(
+    const isUiAvailable = await holochainManager.isUiAvailable(
+      appVersionEntry.bundle_hashes.ui_hash,
+    );
+    const isHappAvailable = await holochainManager.isHappAvailableAndValid(
       appVersionEntry.bundle_hashes.happ_hash,
     );
 
@@ -721,7 +748,7 @@ const router = t.router({
           undefined,
           (status) => LAUNCHER_EMITTER.emit(DOWNLOAD_PROGRESS_UPDATE, status),
         );
-        holochainManager.storeHapp(Array.from(happBytes));
+        await holochainManager.storeHapp(Array.from(happBytes));
         console.log('happ stored.');
       }
       if (!isUiAvailable) {
@@ -731,7 +758,7 @@ const router = t.router({
           undefined,
           (status) => LAUNCHER_EMITTER.emit(DOWNLOAD_PROGRESS_UPDATE, status),
         );
-        holochainManager.storeUiIfNecessary(Array.from(uiBytes), icon);
+        await holochainManager.storeUiIfNecessary(Array.from(uiBytes), icon);
         console.log('UI stored.');
       }
     } catch (error) {
@@ -757,6 +784,32 @@ const router = t.router({
     if (!decodedHappOrWebhapp.uiBytes) throw new Error('File is not a webhapp (no UI).');
     return true;
   }),
+  inspectBundle: t.procedure
+    .input(z.union([z.instanceof(Uint8Array), z.string()]))
+    .query(async (opts) => {
+      const bytesOrPath = typeof opts.input === 'string' ? opts.input : Array.from(opts.input);
+      return rustUtils.inspectBundle(bytesOrPath);
+    }),
+  computeDnaHashes: t.procedure
+    .input(
+      z.object({
+        bytes: z.instanceof(Uint8Array),
+        networkSeed: z.string().optional(),
+        roleModifiers: z
+          .record(
+            z.object({
+              networkSeed: z.string().optional(),
+              properties: z.any().optional(),
+              originTime: z.number().optional(),
+            }),
+          )
+          .optional(),
+      }),
+    )
+    .query(async (opts) => {
+      const { bytes, networkSeed, roleModifiers } = opts.input;
+      return rustUtils.computeDnaHashes(Array.from(bytes), networkSeed, roleModifiers);
+    }),
   isHappAvailableAndValid: t.procedure.input(z.string()).query(async (opts) => {
     const holochainManager = getHolochainManager(DEFAULT_HOLOCHAIN_DATA_ROOT!.name);
     return holochainManager.isHappAvailableAndValid(opts.input);
@@ -778,12 +831,11 @@ const router = t.router({
   installHappFromPath: t.procedure.input(InstallHappFromPathSchema).mutation(async (opts) => {
     try {
       const { filePath, appId, networkSeed, agentPubKey } = opts.input;
-      const happAndUiBytes = await rustUtils.readAndDecodeHappOrWebhapp(filePath);
       const holochainManager = getHolochainManager(DEFAULT_HOLOCHAIN_DATA_ROOT!.name);
       const distributionInfo: DistributionInfoV1 = { type: 'filesystem' };
-      await installApp({
+      await installAppFromPath({
         holochainManager: holochainManager,
-        happAndUiBytes,
+        filePath,
         appId,
         distributionInfo,
         networkSeed,
@@ -830,11 +882,10 @@ const router = t.router({
     try {
       const { name, appId, networkSeed, agentPubKey } = opts.input;
       const filePath = path.join(DEFAULT_APPS_DIRECTORY, name);
-      const happAndUiBytes = await rustUtils.readAndDecodeHappOrWebhapp(filePath);
       const holochainManager = getHolochainManager(DEFAULT_HOLOCHAIN_DATA_ROOT!.name);
-      await installApp({
+      await installAppFromPath({
         holochainManager,
-        happAndUiBytes,
+        filePath,
         appId,
         distributionInfo: { type: DISTRIBUTION_TYPE_DEFAULT_APP },
         networkSeed,
@@ -927,7 +978,7 @@ const router = t.router({
       if (!exportToPathResponse.filePath) throw new Error('File path undefined.');
       fs.writeFileSync(
         exportToPathResponse.filePath,
-        JSON.stringify(keyFile, undefined, 2),
+        await rustUtils.serializeKeyFile(keyFile),
         'utf-8',
       );
       shell.showItemInFolder(exportToPathResponse.filePath);
@@ -1021,12 +1072,50 @@ const router = t.router({
     return createObservableGeneric(LAUNCHER_EMITTER, DOWNLOAD_PROGRESS_UPDATE);
   }),
   deriveAndImportSeedFromJsonFile: t.procedure
-    .input(z.object({ filePath: z.string(), passphrase: z.string().optional() }))
+    .input(
+      z.object({
+        filePath: z.string(),
+        passphrase: z.string().optional(),
+        strict: z.boolean().optional(),
+      }),
+    )
     .mutation(async (opts) => {
       if (!DEFAULT_LAIR_CLIENT) throw new Error('Lair client is not ready.');
       return DEFAULT_LAIR_CLIENT.deriveAndImportSeedFromJsonFile(
         opts.input.filePath,
         opts.input.passphrase,
+        opts.input.strict,
+      );
+    }),
+  parseHostConfigFile: t.procedure
+    .input(z.object({ filePath: z.string() }))
+    .mutation(async (opts) => {
+      const json = fs.readFileSync(opts.input.filePath, 'utf-8');
+      return rustUtils.parseHostConfig(json);
+    }),
+  importHostConfigSeed: t.procedure
+    .input(
+      z.object({
+        hostConfig: z.object({
+          format: z.enum(['v2', 'v3', 'flat']),
+          deviceBundle: z.string(),
+          deviceDerivationPath: z.array(z.number()),
+          revocationPubKey: z.string().optional(),
+          holoportId: z.string().optional(),
+          initialHostPubKey: z.string().optional(),
+          registrationCode: z.string().optional(),
+          adminEmail: z.string().optional(),
+        }),
+        passphrase: z.string().optional(),
+        strict: z.boolean().optional(),
+      }),
+    )
+    .mutation(async (opts) => {
+      if (!DEFAULT_LAIR_CLIENT) throw new Error('Lair client is not ready.');
+      return DEFAULT_LAIR_CLIENT.importHostConfigSeed(
+        opts.input.hostConfig as rustUtils.HostConfig,
+        opts.input.passphrase,
+        opts.input.strict,
       );
     }),
   hideSettingsWindow: t.procedure.subscription(() => {
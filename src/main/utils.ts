@@ -275,6 +275,47 @@ export const installApp = async ({
   }
 };
 
+export const installAppFromPath = async ({
+  holochainManager,
+  filePath,
+  appId,
+  distributionInfo,
+  networkSeed,
+  icon,
+  agentPubKey,
+}: {
+  holochainManager: HolochainManager;
+  filePath: string;
+  appId: string;
+  distributionInfo: DistributionInfoV1;
+  networkSeed: string;
+  icon?: Uint8Array;
+  agentPubKey?: AgentPubKeyB64;
+}): Promise<void> => {
+  const { happSha256, uiZipSha256 } = await holochainManager.storeHappOrWebhappFile(
+    filePath,
+    icon,
+  );
+  if (uiZipSha256) {
+    await holochainManager.installWebhappFromHashes({
+      happSha256,
+      uiZipSha256,
+      appId,
+      distributionInfo,
+      networkSeed,
+      agentPubKey,
+    });
+  } else {
+    await holochainManager.installHeadlessHappFromHash({
+      happSha256,
+      appId,
+      distributionInfo,
+      networkSeed,
+      agentPubKey,
+    });
+  }
+};
+
 export const handleInstallError = (error: unknown) => {
   const errorMessage = getErrorMessage(error);
   if (